@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Defaults for command-line options, read from `$XDG_CONFIG_HOME/lsgres/config.json`
+/// (or `~/.config/lsgres/config.json`); options given on the command line take precedence
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Partition to show unless `--partition` or `--all-partitions` is given
+    pub partition: Option<String>,
+    /// Partitions whose running jobs can be preempted, unless `--preempt-partition` is given
+    pub preempt_partitions: Option<Vec<String>>,
+    /// QOS whose running jobs can be preempted, unless `--preempt-qos` is given
+    pub preempt_qos: Option<Vec<String>>,
+    pub glyph_used: Option<String>,
+    pub glyph_suspended: Option<String>,
+    pub glyph_preempted: Option<String>,
+    pub glyph_idle: Option<String>,
+    pub glyph_drained: Option<String>,
+    pub glyph_offline: Option<String>,
+    pub glyph_powered_down: Option<String>,
+    pub color_used: Option<String>,
+    pub color_suspended: Option<String>,
+    pub color_preempted: Option<String>,
+    pub color_idle: Option<String>,
+    pub color_drained: Option<String>,
+    pub color_offline: Option<String>,
+    pub color_powered_down: Option<String>,
+    /// Name of the palette, e.g. "colorblind"
+    pub palette: Option<String>,
+    pub legend: Option<bool>,
+    pub accessible: Option<bool>,
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("lsgres").join("config.json"))
+}
+
+impl Config {
+    /// Load the configuration file, or the defaults if there is none
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_path().filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Reading config file {} failed", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Parsing config file {} failed", path.display()))
+    }
+}
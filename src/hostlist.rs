@@ -0,0 +1,178 @@
+use anyhow::{bail, Context, Result};
+
+/// Most hostnames a hostlist can expand to before it is taken as corrupt,
+/// Slurm's own limit on the size of a hostlist range
+const MAX_HOSTS: usize = 65536;
+
+/// Split `s` on commas that are not enclosed in brackets
+fn split_top_level(s: &str) -> Result<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth = depth
+                    .checked_sub(1)
+                    .with_context(|| format!("Unmatched ']' in hostlist \"{}\"", s))?
+            }
+            ',' if depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        bail!("Unmatched '[' in hostlist \"{}\"", s);
+    }
+    parts.push(&s[start..]);
+    Ok(parts)
+}
+
+/// Expand the inside of a bracket such as `01-03,7` into `["01", "02", "03", "7"]`
+fn expand_range(range: &str) -> Result<Vec<String>> {
+    let mut values = Vec::new();
+    for part in range.split(',') {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let first: u64 = start
+                    .parse()
+                    .with_context(|| format!("Invalid range start in \"{}\"", part))?;
+                let last: u64 = end
+                    .parse()
+                    .with_context(|| format!("Invalid range end in \"{}\"", part))?;
+                if last < first {
+                    bail!("Reversed range \"{}\" in hostlist", part);
+                }
+                if last - first >= MAX_HOSTS as u64 {
+                    bail!("Too many hosts in range \"{}\" of hostlist", part);
+                }
+                let width = start.len();
+                values.extend((first..=last).map(|i| format!("{:0width$}", i, width = width)));
+            }
+            None => {
+                if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+                    bail!("Invalid range element \"{}\" in hostlist", part);
+                }
+                values.push(part.to_owned());
+            }
+        }
+        if values.len() > MAX_HOSTS {
+            bail!(
+                "More than {} hosts in range \"{}\" of hostlist",
+                MAX_HOSTS,
+                range
+            );
+        }
+    }
+    Ok(values)
+}
+
+/// Expand a single hostlist item that may contain several bracket groups
+fn expand_item(item: &str) -> Result<Vec<String>> {
+    let mut hosts = vec![String::new()];
+    let mut rest = item;
+    while let Some(open) = rest.find('[') {
+        let close = rest[open..]
+            .find(']')
+            .with_context(|| format!("Unmatched '[' in hostlist \"{}\"", item))?
+            + open;
+        let prefix = &rest[..open];
+        let suffixes = expand_range(&rest[open + 1..close])?;
+        // Each bracket group multiplies the hosts of the ones before it
+        if hosts.len() * suffixes.len() > MAX_HOSTS {
+            bail!("More than {} hosts in hostlist \"{}\"", MAX_HOSTS, item);
+        }
+        hosts = hosts
+            .iter()
+            .flat_map(|host| {
+                suffixes
+                    .iter()
+                    .map(move |s| format!("{}{}{}", host, prefix, s))
+            })
+            .collect();
+        rest = &rest[close + 1..];
+    }
+    for host in hosts.iter_mut() {
+        host.push_str(rest);
+    }
+    Ok(hosts)
+}
+
+/// Expand a Slurm hostlist expression such as `gpu-a-[01-03,7],gpu-b-1`
+/// into individual hostnames
+pub fn expand(hostlist: &str) -> Result<Vec<String>> {
+    let mut hosts = Vec::new();
+    for item in split_top_level(hostlist.trim())? {
+        let item = item.trim();
+        if !item.is_empty() {
+            hosts.extend(expand_item(item)?);
+        }
+        if hosts.len() > MAX_HOSTS {
+            bail!("More than {} hosts in hostlist \"{}\"", MAX_HOSTS, hostlist);
+        }
+    }
+    Ok(hosts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padded_ranges() {
+        assert_eq!(
+            expand("gpu-[01-03]").unwrap(),
+            ["gpu-01", "gpu-02", "gpu-03"]
+        );
+        assert_eq!(expand("gpu-[8-10]").unwrap(), ["gpu-8", "gpu-9", "gpu-10"]);
+        assert_eq!(
+            expand("gpu-[098-100]").unwrap(),
+            ["gpu-098", "gpu-099", "gpu-100"]
+        );
+    }
+
+    #[test]
+    fn lists_inside_brackets() {
+        assert_eq!(expand("a[1,3-4]").unwrap(), ["a1", "a3", "a4"]);
+        assert_eq!(
+            expand("gpu-sm01-[13-15],gpu-f-6").unwrap(),
+            ["gpu-sm01-13", "gpu-sm01-14", "gpu-sm01-15", "gpu-f-6"]
+        );
+    }
+
+    #[test]
+    fn several_bracket_groups() {
+        assert_eq!(
+            expand("x[1-2]y[1-2]").unwrap(),
+            ["x1y1", "x1y2", "x2y1", "x2y2"]
+        );
+        assert_eq!(expand("r[1-2]n[01-02]-ib").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn single_names() {
+        assert_eq!(expand("gpu-1").unwrap(), ["gpu-1"]);
+        assert_eq!(expand(" gpu-1, gpu-2 ").unwrap(), ["gpu-1", "gpu-2"]);
+        assert!(expand("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_hostlists() {
+        assert!(expand("gpu-[3-1]").is_err());
+        assert!(expand("gpu-[1,a]").is_err());
+        assert!(expand("gpu-[1,]").is_err());
+        assert!(expand("gpu-[1-2").is_err());
+        assert!(expand("gpu-1-2]").is_err());
+    }
+
+    #[test]
+    fn oversized_hostlists() {
+        assert!(expand("gpu-[0-4294967295]").is_err());
+        assert!(expand("x[0-999]y[0-999]").is_err());
+        assert!(expand("x[0-60000],y[0-60000]").is_err());
+        assert_eq!(expand("x[1-65536]").unwrap().len(), MAX_HOSTS);
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{bail, Context, Result};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Most indices a single GRES entry can have before it is taken as corrupt
+pub const MAX_INDICES: usize = 4096;
+
+/// A set of device indices, such as the GPUs listed in `IDX:0-1,3`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexSet(BTreeSet<u32>);
+
+impl IndexSet {
+    /// Parse a comma-separated list of indices and inclusive ranges
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut set = BTreeSet::new();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('-') {
+                Some((start, end)) => {
+                    let start: u32 = start
+                        .parse()
+                        .with_context(|| format!("Invalid index range \"{}\"", part))?;
+                    let end: u32 = end
+                        .parse()
+                        .with_context(|| format!("Invalid index range \"{}\"", part))?;
+                    if end < start {
+                        bail!("Reversed index range \"{}\"", part);
+                    }
+                    if (end - start) as usize >= MAX_INDICES {
+                        bail!("Too many indices in range \"{}\"", part);
+                    }
+                    set.extend(start..=end);
+                }
+                None => {
+                    set.insert(
+                        part.parse()
+                            .with_context(|| format!("Invalid index \"{}\"", part))?,
+                    );
+                }
+            }
+        }
+        if set.len() > MAX_INDICES {
+            bail!("More than {} indices in \"{}\"", MAX_INDICES, s);
+        }
+        Ok(Self(set))
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Indices in this set that are not in `other`
+    pub fn difference(&self, other: &Self) -> Self {
+        Self(self.0.difference(&other.0).copied().collect())
+    }
+
+    /// Indices in `0..total` that are not in this set
+    pub fn complement(&self, total: u32) -> Self {
+        Self((0..total).filter(|i| !self.0.contains(i)).collect())
+    }
+}
+
+impl fmt::Display for IndexSet {
+    /// Compress consecutive indices into ranges, e.g. `0,2-3,5`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for &i in &self.0 {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == i => *end = i,
+                _ => ranges.push((i, i)),
+            }
+        }
+        let formatted = ranges
+            .iter()
+            .map(|&(start, end)| {
+                if start == end {
+                    start.to_string()
+                } else {
+                    format!("{}-{}", start, end)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        f.write_str(&formatted)
+    }
+}
+
+impl Serialize for IndexSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `s` parsed and formatted again
+    fn compressed(s: &str) -> String {
+        IndexSet::parse(s).unwrap().to_string()
+    }
+
+    #[test]
+    fn compress_ranges() {
+        assert_eq!(compressed("0,2,3,5"), "0,2-3,5");
+        assert_eq!(compressed("5,3,2,0"), "0,2-3,5");
+        assert_eq!(compressed("0-3,2-5"), "0-5");
+        assert_eq!(compressed("7"), "7");
+        assert_eq!(compressed(""), "");
+        assert_eq!(compressed("0, 1 ,4-6"), "0-1,4-6");
+    }
+
+    #[test]
+    fn complement_within_total() {
+        let used = IndexSet::parse("0,3").unwrap();
+        assert_eq!(used.complement(8).to_string(), "1-2,4-7");
+        assert_eq!(used.complement(4).to_string(), "1-2");
+        // Indices past the total are not counted as used
+        assert_eq!(
+            IndexSet::parse("9").unwrap().complement(2).to_string(),
+            "0-1"
+        );
+        assert_eq!(IndexSet::default().complement(4).to_string(), "0-3");
+        assert_eq!(IndexSet::parse("0-3").unwrap().complement(4).count(), 0);
+    }
+
+    #[test]
+    fn difference_and_count() {
+        let all = IndexSet::parse("0-7").unwrap();
+        let used = IndexSet::parse("1,4-5").unwrap();
+        assert_eq!(all.difference(&used).to_string(), "0,2-3,6-7");
+        assert_eq!(all.difference(&used).count(), 5);
+    }
+
+    #[test]
+    fn invalid_indices() {
+        assert!(IndexSet::parse("3-1").is_err());
+        assert!(IndexSet::parse("a").is_err());
+        assert!(IndexSet::parse("0-").is_err());
+        assert!(IndexSet::parse("0-100000").is_err());
+    }
+}
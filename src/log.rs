@@ -0,0 +1,22 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Print a diagnostic message to stderr if `--verbose` was given
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled() {
+            eprintln!("[lsgres] {}", format_args!($($arg)*));
+        }
+    };
+}
+
+pub(crate) use debug;
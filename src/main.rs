@@ -1,166 +1,6005 @@
+mod config;
+mod hostlist;
+mod indexset;
+mod log;
+mod prompt;
+mod time;
+
 use anyhow::{bail, Context, Result};
-use clap::{Parser, ValueEnum};
-use colored::{ColoredString, Colorize};
+use clap::{builder::PossibleValuesParser, Parser, Subcommand, ValueEnum};
+use colored::{Color, ColoredString, Colorize};
+use config::Config;
+use indexset::{IndexSet, MAX_INDICES};
+use log::debug;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::Deserialize;
-use tabled::{settings::Style, Table, Tabled};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use tabled::{
+    grid::util::string::get_string_width,
+    settings::{
+        location::{ByColumnName, Locator},
+        Alignment, Disable, Format, Style, Width,
+    },
+    Table, Tabled,
+};
+use unicode_width::UnicodeWidthChar;
+
+/// Slurm's sentinel for a numeric value that was never set
+const NO_VAL: u64 = 0xfffffffe;
+
+/// Slurm reports memory in binary megabytes
+const MB_PER_GB: usize = 1024;
+
+/// A number that Slurm reports either bare or, in newer versions,
+/// wrapped as `{"set": .., "infinite": .., "number": ..}`
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(untagged)]
+enum SlurmNumber {
+    Plain(u64),
+    Wrapped {
+        set: bool,
+        infinite: bool,
+        number: u64,
+    },
+}
+
+impl SlurmNumber {
+    /// The value if it is set, finite and not the `NO_VAL` sentinel
+    fn value(self) -> Option<u64> {
+        match self {
+            Self::Plain(number) if number != NO_VAL => Some(number),
+            Self::Wrapped {
+                set: true,
+                infinite: false,
+                number,
+            } if number != NO_VAL => Some(number),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Deserialize, Debug)]
 struct Node {
     hostname: String,
+    #[serde(deserialize_with = "deserialize_node_state")]
     state: Vec<String>,
     partitions: Vec<String>,
+    #[serde(deserialize_with = "deserialize_count")]
     cpus: usize,
+    #[serde(deserialize_with = "deserialize_count")]
     alloc_idle_cpus: usize,
+    /// Memory in MB
+    #[serde(deserialize_with = "deserialize_count")]
     real_memory: usize,
+    #[serde(deserialize_with = "deserialize_count")]
     alloc_memory: usize,
+    /// Memory in MB reserved for the system by `MemSpecLimit`, which jobs cannot use
+    #[serde(default, deserialize_with = "deserialize_count")]
+    mem_spec_limit: usize,
     gres: String,
     gres_used: String,
+    /// GRES drained individually while the node stays up, `N/A` if none
+    #[serde(default, alias = "gres_drained")]
+    gres_drain: String,
+    #[serde(default)]
+    active_features: Vec<String>,
+    /// CPU load average multiplied by 100
+    cpu_load: Option<SlurmNumber>,
+    #[serde(default)]
+    address: String,
+    /// Host name reported by slurmd, which may differ from the Slurm node name
+    #[serde(default)]
+    node_hostname: String,
+    boot_time: Option<SlurmNumber>,
+    slurmd_start_time: Option<SlurmNumber>,
+    /// Version of the running slurmd
+    #[serde(default)]
+    version: String,
+    /// Configured trackable resources, e.g. `cpu=64,mem=500G,gres/gpu=4`
+    #[serde(default)]
+    tres: String,
+    #[serde(default)]
+    tres_used: String,
+    /// Scheduling weight, where Slurm prefers lower weights
+    weight: Option<SlurmNumber>,
+    #[serde(default)]
+    boards: usize,
+    #[serde(default)]
+    sockets: usize,
+    /// Cores per socket
+    #[serde(default)]
+    cores: usize,
+    /// Threads per core
+    #[serde(default)]
+    threads: usize,
+    /// Why the node was drained or set down, if it was
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    reason_set_by_user: String,
 }
 
 #[derive(Deserialize, Debug)]
 struct SlurmNodes {
+    #[serde(default)]
+    meta: Meta,
     nodes: Vec<Node>,
+    /// Time of the controller's last node state update
+    last_update: Option<SlurmNumber>,
+}
+
+/// Information about the responding Slurm controller
+#[derive(Deserialize, Debug, Default)]
+struct Meta {
+    /// Named `Slurm` before Slurm 23.11
+    #[serde(alias = "Slurm")]
+    slurm: Option<MetaSlurm>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetaSlurm {
+    cluster: Option<String>,
+}
+
+impl Meta {
+    fn cluster(&self) -> Option<&str> {
+        self.slurm.as_ref()?.cluster.as_deref()
+    }
+}
+
+/// Accept either a single string (older Slurm) or a list of strings
+fn deserialize_string_or_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        String(String),
+        List(Vec<String>),
+    }
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::String(s) => vec![s],
+        StringOrList::List(list) => list,
+    })
+}
+
+/// Accept node states as a list such as `["MIXED", "DRAIN"]`, or as a single
+/// string such as `mixed+drain` from older Slurm
+fn deserialize_node_state<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(deserialize_string_or_list(deserializer)?
+        .iter()
+        .flat_map(|state| state.split('+'))
+        .filter(|state| !state.is_empty())
+        .map(str::to_uppercase)
+        .collect())
+}
+
+/// Accept a count either bare or wrapped like a `SlurmNumber`, treating unset as zero
+fn deserialize_count<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let number = SlurmNumber::deserialize(deserializer)?;
+    Ok(number.value().unwrap_or(0) as usize)
+}
+
+#[derive(Deserialize, Debug)]
+struct Job {
+    #[serde(default)]
+    job_id: u64,
+    #[serde(deserialize_with = "deserialize_string_or_list")]
+    job_state: Vec<String>,
+    /// Hostlist expression of the allocated nodes, e.g. `gpu-a-[1-3]`
+    #[serde(default)]
+    nodes: String,
+    #[serde(default)]
+    user_name: String,
+    #[serde(default)]
+    partition: String,
+    /// Quality of service, which can make a job preemptible in any partition
+    #[serde(default)]
+    qos: String,
+    /// GRES allocated on each node, in the order of the node list
+    #[serde(default)]
+    gres_detail: Vec<String>,
+    /// Expected end based on the time limit, unset or infinite when unlimited
+    end_time: Option<SlurmNumber>,
+    /// Per-node resources, where some Slurm versions report GRES instead of `gres_detail`
+    job_resources: Option<JobResources>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JobResources {
+    #[serde(default)]
+    allocated_nodes: Vec<AllocatedNode>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AllocatedNode {
+    nodename: String,
+    #[serde(default)]
+    gres: String,
+}
+
+impl Job {
+    fn is_running(&self) -> bool {
+        self.job_state.iter().any(|s| s == "RUNNING")
+    }
+
+    /// Whether the job was suspended, e.g. by preemption, keeping its GRES allocated
+    fn is_suspended(&self) -> bool {
+        self.job_state.iter().any(|s| s == "SUSPENDED")
+    }
+
+    /// Number of GRES of each model of a type allocated to the job on each of its nodes
+    fn allocations(&self, gres_type: &str) -> Result<Vec<(String, String, usize)>> {
+        if self.gres_detail.is_empty() {
+            debug!(
+                "Job on {} has no gres_detail, using job_resources instead",
+                self.nodes
+            );
+            let allocated_nodes = self
+                .job_resources
+                .as_ref()
+                .map_or(&[][..], |resources| &resources.allocated_nodes);
+            return Ok(allocated_nodes
+                .iter()
+                .flat_map(|node| {
+                    parse_allocations(&node.gres, gres_type)
+                        .map(|(model, count)| (node.nodename.clone(), model, count))
+                })
+                .collect());
+        }
+        // gres_detail has one entry per node of the expanded nodelist
+        let hosts = hostlist::expand(&self.nodes)?;
+        if hosts.len() != self.gres_detail.len() {
+            debug!(
+                "Job on {} has {} nodes but {} gres_detail entries",
+                self.nodes,
+                hosts.len(),
+                self.gres_detail.len()
+            );
+        }
+        Ok(hosts
+            .into_iter()
+            .zip(&self.gres_detail)
+            .flat_map(|(host, detail)| {
+                parse_allocations(detail, gres_type)
+                    .map(move |(model, count)| (host.clone(), model, count))
+            })
+            .collect())
+    }
+}
+
+/// Partition whose jobs can be preempted by other jobs, unless others are configured
+const PREEMPTED_PARTITION: &str = "preempted";
+
+/// Environment variable with comma-separated preempt partitions, e.g. "scavenger,preemptable"
+const PREEMPT_PARTITION_ENV: &str = "LSGRES_PREEMPT_PARTITION";
+
+/// Which running jobs can be preempted, by their partition or their QOS
+#[derive(Serialize, Debug)]
+struct Preemption {
+    partitions: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    qos: Vec<String>,
+}
+
+impl Preemption {
+    /// The partitions and QOS that Slurm's configuration makes preemptible, see
+    /// `SlurmPartitions::preemptible` and `SlurmQos::preemptible`, with the `preempted`
+    /// partition if the partitions do not report preempt modes
+    fn detect(slurm_partitions: Option<&SlurmPartitions>, slurm_qos: Option<&SlurmQos>) -> Self {
+        let partitions = match slurm_partitions.and_then(SlurmPartitions::preemptible) {
+            Some(partitions) => {
+                debug!("Detected preemptible partitions: {:?}", partitions);
+                partitions
+            }
+            None => vec![PREEMPTED_PARTITION.to_owned()],
+        };
+        let qos = slurm_qos.map(SlurmQos::preemptible).unwrap_or_default();
+        if !qos.is_empty() {
+            debug!("Detected preemptible QOS: {:?}", qos);
+        }
+        Self { partitions, qos }
+    }
+
+    /// Whether any job can be preemptible, so that jobs are worth querying: a QOS is
+    /// preemptible or a preemptible partition exists, which is assumed without partitions
+    fn applies(&self, slurm_partitions: Option<&SlurmPartitions>) -> bool {
+        !self.qos.is_empty()
+            || slurm_partitions.is_none_or(|slurm_partitions| {
+                slurm_partitions
+                    .partitions
+                    .iter()
+                    .any(|p| self.partitions.contains(&p.name))
+            })
+    }
+
+    fn is_preemptible(&self, job: &Job) -> bool {
+        self.partitions.contains(&job.partition)
+            || (!job.qos.is_empty() && self.qos.contains(&job.qos))
+    }
+
+    /// The partitions and QOS, e.g. `scavenger, QOS low`
+    fn describe(&self) -> String {
+        let mut sources = self.partitions.clone();
+        sources.extend(self.qos.iter().map(|qos| format!("QOS {}", qos)));
+        if sources.is_empty() {
+            "no partition".to_owned()
+        } else {
+            sources.join(", ")
+        }
+    }
+}
+
+/// Count GPU indices such as `0-1,3`
+fn count_gpu_indices(indices: &str) -> Result<usize> {
+    let mut count = 0;
+    for part in indices.split(',').map(str::trim) {
+        if part.is_empty() {
+            bail!("Empty index in \"{}\"", indices);
+        }
+        count += match part.split_once('-') {
+            Some((start, end)) => {
+                let start: usize = start
+                    .parse()
+                    .with_context(|| format!("Invalid index range \"{}\"", part))?;
+                let end: usize = end
+                    .parse()
+                    .with_context(|| format!("Invalid index range \"{}\"", part))?;
+                if end < start {
+                    bail!("Reversed index range \"{}\"", part);
+                }
+                if end - start >= MAX_INDICES {
+                    bail!("Too many indices in range \"{}\"", part);
+                }
+                end - start + 1
+            }
+            None => {
+                part.parse::<usize>()
+                    .with_context(|| format!("Invalid index \"{}\"", part))?;
+                1
+            }
+        };
+        if count > MAX_INDICES {
+            bail!("More than {} indices in \"{}\"", MAX_INDICES, indices);
+        }
+    }
+    Ok(count)
+}
+
+/// Split a GRES list such as `gpu:a40:2(IDX:0,3),gpu:a100:1` on the commas
+/// that are not within parentheses
+fn split_gres_entries(gres: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in gres.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                entries.push(&gres[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&gres[start..]);
+    entries.retain(|entry| !entry.trim().is_empty());
+    entries
+}
+
+/// Model and number of GRES in a job's GRES entry of a type, such as
+/// `gpu:a100:2(IDX:0-1)`, `gpu:4` or `shard:32`
+fn parse_allocation(detail: &str, gres_type: &str) -> Option<(String, usize)> {
+    if self::gres_type(detail) != gres_type {
+        return None;
+    }
+    // Suffixes such as `(S:0-1)` and `(IDX:0-1)` follow the count in either order
+    let (model, count) = detail.split('(').next()?.rsplit_once(':')?;
+    if let Some((_, indices)) = detail.split_once("(IDX:") {
+        let indices = indices.split(')').next().unwrap_or_default();
+        return match count_gpu_indices(indices) {
+            Ok(count) => Some((model.to_owned(), count)),
+            Err(err) => {
+                debug!("Skipping GRES allocation \"{}\": {:#}", detail, err);
+                None
+            }
+        };
+    }
+    // Typeless GRES such as `gpu:4` have no model, which matches them to typeless nodes
+    debug!("No indices in \"{}\", using the count instead", detail);
+    let count = count.parse().ok();
+    if count.is_none() {
+        debug!("Cannot parse the count in \"{}\"", detail);
+    }
+    count.map(|count| (model.to_owned(), count))
+}
+
+/// Models and numbers of GRES of a type in a job's gres_detail, which can list
+/// several models
+fn parse_allocations<'a>(
+    detail: &'a str,
+    gres_type: &'a str,
+) -> impl Iterator<Item = (String, usize)> + 'a {
+    split_gres_entries(detail)
+        .into_iter()
+        .filter_map(move |entry| parse_allocation(entry, gres_type))
+}
+
+/// GRES of a type held by running preemptible jobs, keyed by hostname and model
+fn process_preempted_jobs(
+    jobs: &[Job],
+    gres_type: &str,
+    preemption: &Preemption,
+) -> Result<HashMap<String, HashMap<String, usize>>> {
+    sum_allocations(
+        jobs.iter()
+            .filter(|job| job.is_running() && preemption.is_preemptible(job)),
+        gres_type,
+    )
+}
+
+/// GRES of a type held by `jobs`, keyed by hostname and model
+fn sum_allocations<'a>(
+    jobs: impl Iterator<Item = &'a Job>,
+    gres_type: &str,
+) -> Result<HashMap<String, HashMap<String, usize>>> {
+    let mut held: HashMap<String, HashMap<String, usize>> = HashMap::new();
+    for job in jobs {
+        for (host, model, count) in job.allocations(gres_type)? {
+            *held.entry(host).or_default().entry(model).or_insert(0) += count;
+        }
+    }
+    Ok(held)
+}
+
+#[derive(Deserialize, Debug)]
+struct SlurmJobs {
+    jobs: Vec<Job>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Reservation {
+    name: String,
+    /// Hostlist expression of the reserved nodes
+    #[serde(default)]
+    node_list: String,
+    #[serde(default, deserialize_with = "deserialize_string_or_list")]
+    flags: Vec<String>,
+    start_time: Option<SlurmNumber>,
+    end_time: Option<SlurmNumber>,
+}
+
+impl Reservation {
+    fn is_active(&self, now: u64) -> bool {
+        let start = self.start_time.and_then(SlurmNumber::value).unwrap_or(0);
+        let end = self.end_time.and_then(SlurmNumber::value);
+        start <= now && end.is_none_or(|end| now < end)
+    }
+
+    fn is_maintenance(&self) -> bool {
+        self.flags
+            .iter()
+            .flat_map(|f| f.split(','))
+            .any(|f| f.trim() == "MAINT")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SlurmReservations {
+    reservations: Vec<Reservation>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SlurmPartitions {
+    partitions: Vec<Partition>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Partition {
+    name: String,
+    /// Priority tier as reported by older Slurm versions
+    #[serde(default)]
+    priority_tier: Option<u64>,
+    #[serde(default)]
+    priority: Option<PartitionPriority>,
+    /// How jobs are preempted, e.g. `REQUEUE`, or `OFF` if they are not
+    #[serde(
+        default,
+        alias = "preemption_mode",
+        deserialize_with = "deserialize_string_or_list"
+    )]
+    preempt_mode: Vec<String>,
+    /// Hostlist expression of the partition's nodes
+    #[serde(default, deserialize_with = "deserialize_partition_nodes")]
+    nodes: String,
+}
+
+/// Accept the nodes of a partition either as a hostlist (older Slurm) or as
+/// `{"configured": .., "total": ..}`
+fn deserialize_partition_nodes<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PartitionNodes {
+        String(String),
+        Detail {
+            #[serde(default)]
+            configured: String,
+        },
+    }
+    Ok(match PartitionNodes::deserialize(deserializer)? {
+        PartitionNodes::String(nodes) => nodes,
+        PartitionNodes::Detail { configured } => configured,
+    })
+}
+
+/// Output of `sacctmgr show qos --json`
+#[derive(Deserialize, Debug)]
+struct SlurmQos {
+    #[serde(default)]
+    qos: Vec<Qos>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Qos {
+    name: String,
+    #[serde(default)]
+    preempt: QosPreempt,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct QosPreempt {
+    /// QOS whose jobs this QOS's jobs can preempt
+    #[serde(default)]
+    list: Vec<String>,
+}
+
+impl SlurmQos {
+    /// QOS whose jobs can be preempted, which are those that another QOS lists as
+    /// preemptable. This only takes effect with `PreemptType=preempt/qos`, which
+    /// sites set when they configure preempt lists.
+    fn preemptible(&self) -> Vec<String> {
+        let preemptible: BTreeSet<&str> = self
+            .qos
+            .iter()
+            .flat_map(|qos| {
+                qos.preempt
+                    .list
+                    .iter()
+                    .filter(move |&name| name != &qos.name)
+            })
+            .map(String::as_str)
+            .collect();
+        self.qos
+            .iter()
+            .filter(|qos| preemptible.contains(qos.name.as_str()))
+            .map(|qos| qos.name.clone())
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct PartitionPriority {
+    #[serde(default)]
+    tier: Option<u64>,
+}
+
+impl Partition {
+    /// Jobs in a partition of a higher priority tier can preempt those in lower ones
+    fn priority_tier(&self) -> u64 {
+        self.priority
+            .as_ref()
+            .and_then(|priority| priority.tier)
+            .or(self.priority_tier)
+            .unwrap_or(0)
+    }
+
+    /// Whether the preempt mode cancels, requeues or suspends preempted jobs,
+    /// rather than being `OFF`
+    fn preempts_jobs(&self) -> bool {
+        self.preempt_mode
+            .iter()
+            .flat_map(|m| m.split(','))
+            .any(|m| {
+                matches!(
+                    m.trim().to_uppercase().as_str(),
+                    "CANCEL" | "REQUEUE" | "SUSPEND"
+                )
+            })
+    }
+
+    fn node_set(&self) -> BTreeSet<String> {
+        match hostlist::expand(&self.nodes) {
+            Ok(nodes) => nodes.into_iter().collect(),
+            Err(err) => {
+                debug!(
+                    "Cannot expand the nodes of partition {}: {:#}",
+                    self.name, err
+                );
+                BTreeSet::new()
+            }
+        }
+    }
+}
+
+impl SlurmPartitions {
+    /// Partitions whose jobs can be preempted, unknown if Slurm does not report preempt
+    /// modes. With partition priority preemption, jobs are only preempted by jobs of a
+    /// partition in a higher priority tier that shares nodes with theirs, and only if
+    /// their preempt mode cancels, requeues or suspends them. As a cluster-wide
+    /// `PreemptMode` shows up as the mode of every partition, sharing nodes is what tells
+    /// the partitions that can actually be preempted. The `preempted` partition is kept
+    /// by its name, as sites also use it for QOS-based preemption.
+    fn preemptible(&self) -> Option<Vec<String>> {
+        if self.partitions.iter().all(|p| p.preempt_mode.is_empty()) {
+            return None;
+        }
+        let nodes: Vec<BTreeSet<String>> =
+            self.partitions.iter().map(Partition::node_set).collect();
+        let partitions = || self.partitions.iter().zip(&nodes);
+        Some(
+            partitions()
+                .filter(|&(p, own)| {
+                    p.name == PREEMPTED_PARTITION
+                        || (p.preempts_jobs()
+                            && partitions().any(|(other, other_nodes)| {
+                                other.priority_tier() > p.priority_tier()
+                                    && !own.is_disjoint(other_nodes)
+                            }))
+                })
+                .map(|(p, _)| p.name.clone())
+                .collect(),
+        )
+    }
+
+    /// Whether jobs in `partition` can preempt those in all of the preempt partitions
+    fn can_preempt(&self, partition: &str, preempt_partitions: &[String]) -> Result<bool> {
+        let tier = |name: &str| {
+            self.partitions
+                .iter()
+                .find(|p| p.name == name)
+                .map(Partition::priority_tier)
+        };
+        let Some(own) = tier(partition) else {
+            bail!(
+                "Unknown partition \"{}\", available partitions: {}",
+                partition,
+                self.partitions
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            );
+        };
+        let preempted: Vec<_> = preempt_partitions
+            .iter()
+            .filter_map(|name| tier(name))
+            .collect();
+        Ok(!preempted.is_empty() && preempted.iter().all(|&preempted| own > preempted))
+    }
+}
+
+/// An active reservation covering a node
+#[derive(Serialize, Clone, PartialEq)]
+struct NodeReservation {
+    name: String,
+    maintenance: bool,
+}
+
+/// Active reservations keyed by hostname
+#[derive(Default)]
+struct ReservationSummary {
+    nodes: HashMap<String, Vec<NodeReservation>>,
+}
+
+impl ReservationSummary {
+    fn from_reservations(reservations: &[Reservation], now: u64) -> Result<Self> {
+        let mut summary = Self::default();
+        for reservation in reservations.iter().filter(|r| r.is_active(now)) {
+            let entry = NodeReservation {
+                name: reservation.name.clone(),
+                maintenance: reservation.is_maintenance(),
+            };
+            for host in hostlist::expand(&reservation.node_list)? {
+                summary.nodes.entry(host).or_default().push(entry.clone());
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// Per-node information derived from the job list
+#[derive(Default)]
+struct JobSummary {
+    running_jobs: HashMap<String, usize>,
+    users: HashMap<String, BTreeSet<String>>,
+    preempted_gpus: HashMap<String, HashMap<String, usize>>,
+    /// GRES that suspended jobs keep allocated, keyed by hostname and model
+    suspended_gpus: HashMap<String, HashMap<String, usize>>,
+    /// Earliest end time of the GPU jobs on each node, `None` if all are unlimited
+    next_gpu_release: HashMap<String, Option<u64>>,
+}
+
+impl JobSummary {
+    /// Summarize the jobs, counting the GRES of `gres_type`
+    fn from_jobs(jobs: &[Job], gres_type: &str, preemption: &Preemption) -> Result<Self> {
+        let mut summary = Self {
+            preempted_gpus: process_preempted_jobs(jobs, gres_type, preemption)?,
+            suspended_gpus: sum_allocations(
+                jobs.iter().filter(|job| job.is_suspended()),
+                gres_type,
+            )?,
+            ..Self::default()
+        };
+        for job in jobs.iter().filter(|job| job.is_running()) {
+            let end_time = job.end_time.and_then(SlurmNumber::value);
+            for (host, _, count) in job.allocations(gres_type)? {
+                if count > 0 {
+                    let release = summary.next_gpu_release.entry(host).or_insert(end_time);
+                    *release = match (*release, end_time) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+            }
+            for host in hostlist::expand(&job.nodes)? {
+                *summary.running_jobs.entry(host.clone()).or_insert(0) += 1;
+                summary
+                    .users
+                    .entry(host)
+                    .or_default()
+                    .insert(job.user_name.clone());
+            }
+        }
+        Ok(summary)
+    }
 }
 
 struct GresStatus {
     model: String,
     count: usize,
+    /// Indices listed in an `(IDX:...)` suffix, if any
+    indices: Option<IndexSet>,
+    /// Sockets the GRES are attached to, listed in an `(S:...)` suffix, if any
+    sockets: Option<IndexSet>,
 }
 
 impl GresStatus {
+    /// GRES of a model without indices or sockets
+    fn new(model: &str, count: usize) -> Self {
+        Self {
+            model: model.to_owned(),
+            count,
+            indices: None,
+            sockets: None,
+        }
+    }
+
+    /// Parse the entries of a GRES list of one type, e.g. both models of
+    /// `gpu:a40:4,gpu:a100:2,nvme:1` for `gpu`. Entries flagged `no_consume`,
+    /// such as `bandwidth:no_consume:4G`, are never allocated and would always
+    /// look idle, so they are left out. Unrecognized entries are skipped
+    /// rather than failing the whole table.
+    fn parse_list(s: &str, gres_type: &str) -> Vec<Self> {
+        split_gres_entries(s)
+            .into_iter()
+            .filter(|entry| self::gres_type(entry.trim()) == gres_type)
+            .filter(|entry| {
+                let no_consume = entry.split(':').any(|part| part == "no_consume");
+                if no_consume {
+                    debug!("Ignoring no_consume GRES \"{}\"", entry);
+                }
+                !no_consume
+            })
+            .filter_map(|entry| match Self::from_str(entry) {
+                Ok(status) => Some(status),
+                Err(err) => {
+                    debug!("Ignoring GRES entry \"{}\": {:#}", entry, err);
+                    None
+                }
+            })
+            .collect()
+    }
+
     fn from_str(s: &str) -> Result<Self> {
-        static RE: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"(?P<model>\w+:\w+):(?P<count>\d+)").unwrap());
-        let caps = RE.captures(s).context("Matching Gres status failed!")?;
+        // Nodes without any GRES, such as CPU-only nodes
+        if s.is_empty() || s == "(null)" || s == "N/A" {
+            return Ok(Self::new("", 0));
+        }
+        static RE: Lazy<Regex> = Lazy::new(|| {
+            // The model is optional, e.g. `gpu:8` on clusters that do not configure GPU types,
+            // and MIG slices have dots in it, e.g. `gpu:a100_1g.5gb:28`;
+            // the entry may carry a socket affinity such as `(S:0-1)` and/or an index list
+            Regex::new(r"^(?P<model>\w+(:[\w.-]+)?):(?P<count>\d+)(?P<suffixes>(\([^)]*\))*)$")
+                .unwrap()
+        });
+        let caps = RE
+            .captures(s.trim())
+            .with_context(|| format!("Unrecognized GRES entry \"{}\"", s))?;
+        let mut idx = None;
+        let mut sockets = None;
+        for suffix in caps["suffixes"]
+            .split(')')
+            .filter_map(|suffix| suffix.strip_prefix('('))
+        {
+            match suffix.split_once(':') {
+                Some(("IDX", indices)) => idx = Some(indices),
+                Some(("S", indices)) => sockets = Some(IndexSet::parse(indices)?),
+                // Shards used of each GPU, e.g. `(4/16,0/16)`
+                None if suffix.contains('/') => {}
+                _ => debug!("Ignoring unknown suffix \"({})\" of GRES \"{}\"", suffix, s),
+            }
+        }
+        let field_count = || caps["count"].parse::<usize>();
+        let (count, indices) = match idx {
+            // The index list is authoritative when the summary count disagrees with it
+            Some(idx) if idx != "N/A" => {
+                let indices = IndexSet::parse(idx)?;
+                let count = field_count()?;
+                if count != indices.count() {
+                    debug!(
+                        "GRES \"{}\" lists {} indices for a count of {}, using the indices",
+                        s,
+                        indices.count(),
+                        count
+                    );
+                }
+                (indices.count(), Some(indices))
+            }
+            Some(_) => (field_count()?, Some(IndexSet::default())),
+            None => (field_count()?, None),
+        };
         Ok(Self {
             model: caps["model"].to_string(),
-            count: caps["count"].parse::<usize>()?,
+            count,
+            indices,
+            sockets,
         })
     }
 }
 
-fn repeat_colored_char(character: char, number: usize, color: &str) -> ColoredString {
-    std::iter::repeat(character)
-        .take(number)
+/// Type of a GRES model, e.g. `gpu` for `gpu:a100`
+fn gres_type(model: &str) -> &str {
+    model
+        .split_once(':')
+        .map_or(model, |(gres_type, _)| gres_type)
+}
+
+/// Whether a model is a MIG slice of a GPU, e.g. `gpu:a100_1g.5gb` or `gpu:a100-3g.20gb`
+fn is_mig_model(model: &str) -> bool {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\dg\.\d+gb$").unwrap());
+    RE.is_match(model)
+}
+
+/// Name of a GRES model without its type, e.g. `a100` for `gpu:a100`
+fn model_name(model: &str) -> &str {
+    model.split_once(':').map_or(model, |(_, name)| name)
+}
+
+fn repeat_colored_char(character: char, number: usize, color: Color) -> ColoredString {
+    std::iter::repeat_n(character, number)
         .collect::<String>()
         .color(color)
 }
 
-fn format_ratio(used: usize, total: usize) -> String {
-    format!("{}/{}", used, total)
+/// Split `width` cells proportionally to `counts` with the largest remainder
+/// method, so that the cells always add up to `width`
+fn scale_to_width<const N: usize>(counts: [usize; N], width: usize) -> [usize; N] {
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return [0; N];
+    }
+    let mut cells = counts.map(|count| count * width / total);
+    let mut by_remainder: [usize; N] = std::array::from_fn(|i| i);
+    by_remainder.sort_by_key(|&i| std::cmp::Reverse(counts[i] * width % total));
+    let missing = width - cells.iter().sum::<usize>();
+    for &i in by_remainder.iter().take(missing) {
+        cells[i] += 1;
+    }
+    cells
 }
 
-#[derive(Tabled)]
-struct TableNode {
-    hostname: String,
-    cpus_available: String,
-    memory_available: String,
-    gres: String,
-    gres_status: String,
-    state: String,
+/// Whether the locale promises a UTF-8 capable terminal
+fn locale_is_utf8() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
 }
 
-impl TableNode {
-    fn from_node(node: &Node) -> Result<Self> {
-        let gres_total = GresStatus::from_str(&node.gres)?;
-        let gres_used = GresStatus::from_str(&node.gres_used)?;
-        let idle_count = gres_total.count - gres_used.count;
-        let used_print = repeat_colored_char('u', gres_used.count, "red");
-        let idle_print = repeat_colored_char('i', idle_count, "green");
-        let state_colored = node
-            .state
-            .iter()
-            .map(|s| match s.as_str() {
-                "IDLE" => s.green().to_string(),
-                "MIXED" => s.blue().to_string(),
-                "ALLOCATED" => s.magenta().to_string(),
-                "DRAIN" => s.yellow().to_string(),
-                "DOWN" => s.red().to_string(),
-                _ => s.to_owned(),
-            })
-            .collect::<Vec<String>>()
-            .join(",");
-        Ok(Self {
-            hostname: node.hostname.clone(),
-            cpus_available: format_ratio(node.alloc_idle_cpus, node.cpus),
-            memory_available: format_ratio(
-                (node.real_memory - node.alloc_memory) / 1000,
-                node.real_memory / 1000,
-            ) + "G",
-            gres: gres_total.model,
-            gres_status: format!("{}{}", used_print, idle_print),
-            state: state_colored,
-        })
-    }
-}
-
-fn query_nodes() -> Result<SlurmNodes> {
-    let scontrol = "scontrol";
-    let args = ["show", "nodes", "--json"];
-    let output = std::process::Command::new(scontrol).args(args).output()?;
-    if output.status.success() {
-        let output_str = std::str::from_utf8(&output.stdout)?;
-        let result: SlurmNodes = serde_json::from_str(output_str)?;
-        Ok(result)
+/// Number of categories of GRES in the gres_status column: used, suspended,
+/// preempted, idle, drained, offline and powered down
+const CATEGORIES: usize = 7;
+
+/// Most glyphs printed for a node before each one stands for several GRES
+const MAX_GLYPHS: usize = 32;
+
+/// One glyph per `scale` GRES of each category, rounding up so that no
+/// category with GRES disappears, followed by e.g. `(×4)` when scaled.
+/// Without a fixed scale, the smallest one that fits `MAX_GLYPHS` is used.
+fn format_gres_status(
+    counts: [usize; CATEGORIES],
+    scale: Option<usize>,
+    glyphs: &Glyphs,
+    colorize: bool,
+) -> String {
+    let total: usize = counts.iter().sum();
+    // Rounding each category up adds less than one glyph per category
+    let scale = scale.unwrap_or_else(|| total.div_ceil(MAX_GLYPHS - (CATEGORIES - 1)).max(1));
+    let glyphs = glyphs
+        .categories()
+        .iter()
+        .zip(counts)
+        .map(|(&(character, color), count)| {
+            let number = count.div_ceil(scale);
+            if colorize {
+                repeat_colored_char(character, number, color).to_string()
+            } else {
+                std::iter::repeat_n(character, number).collect()
+            }
+        })
+        .collect::<String>();
+    if scale > 1 {
+        format!("{}(×{})", glyphs, scale)
     } else {
-        let error_msg = String::from_utf8(output.stderr)?;
-        bail!("Scontrol failed: {}", &error_msg)
+        glyphs
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum TableStyle {
-    Markdown,
-    Ascii,
-    Modern,
+/// Characters of the bar for each category of GRES
+fn bar_chars(ascii: bool) -> [char; CATEGORIES] {
+    if ascii {
+        ['#', '=', '%', '.', 'd', 'x', '~']
+    } else {
+        ['█', '▓', '▒', '░', '▚', '×', '▁']
+    }
 }
 
-fn apply_style_to_table(style: Option<TableStyle>, table: &mut Table) -> &Table {
-    match style {
-        Some(TableStyle::Markdown) | None => table.with(Style::markdown()),
-        Some(TableStyle::Ascii) => table.with(Style::ascii()),
-        Some(TableStyle::Modern) => table.with(Style::modern()),
+/// A fixed-width bar of the GRES in each category
+fn format_gres_bar(
+    counts: [usize; CATEGORIES],
+    width: usize,
+    ascii: bool,
+    glyphs: &Glyphs,
+) -> String {
+    if counts.iter().sum::<usize>() == 0 {
+        return "-".to_owned();
     }
+    let cells = scale_to_width(counts, width);
+    bar_chars(ascii)
+        .iter()
+        .zip(glyphs.categories())
+        .zip(cells)
+        .map(|((&character, (_, color)), cells)| {
+            repeat_colored_char(character, cells, color).to_string()
+        })
+        .collect()
 }
 
-#[derive(Parser)]
-#[command(
-    version,
-    about = "List generic resource (GRES) in a Slurm cluster by node"
-)]
-struct Cli {
-    /// Name of the GRES, e.g. "gpu", "h100", "a6000"
-    gres: String,
+fn format_ratio(used: usize, total: usize) -> String {
+    format!("{}/{}", used, total)
+}
 
-    /// Selet which partition to show, e.g. "gpu", "interactive"
-    #[arg(short, long)]
-    partition: Option<String>,
+/// Color `text` green below 50% usage, yellow below 90%, and red otherwise
+fn color_by_usage(text: &str, used_percent: usize, palette: Palette) -> String {
+    let color = match used_percent {
+        0..50 => Color::Green,
+        50..90 => Color::Yellow,
+        _ => Color::Red,
+    };
+    text.color(palette.color(color)).to_string()
+}
 
-    /// Style of the printed table, by default "markdown"
-    #[arg(short, long, value_enum)]
-    style: Option<TableStyle>,
+/// Used GRES as a percentage of the total, or `None` without any GRES
+fn usage_percent(used: usize, total: usize) -> Option<usize> {
+    (total > 0).then(|| used * 100 / total)
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let slurm_nodes = query_nodes()?;
-    let matched: Result<Vec<TableNode>> = slurm_nodes
-        .nodes
+/// Megabytes as gigabytes with `precision` decimals, e.g. `1.9` for 1999 MB
+fn format_memory(mb: usize, precision: usize) -> String {
+    format!("{:.*}", precision, mb as f64 / MB_PER_GB as f64)
+}
+
+/// `part` as a percentage of `total` rounded to the nearest integer, or `None` if
+/// the total is zero
+fn rounded_percent(part: usize, total: usize) -> Option<usize> {
+    (total > 0).then(|| (part * 100 + total / 2) / total)
+}
+
+fn truncate_with_ellipsis(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        return s.to_owned();
+    }
+    let kept: String = s.chars().take(width.saturating_sub(1)).collect();
+    format!("{}…", kept)
+}
+
+/// Join items with commas, replacing those that do not fit in `width` with "+N more"
+fn truncate_list(items: &[String], width: usize) -> String {
+    let mut joined = String::new();
+    for (i, item) in items.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        let remaining = items.len() - i - 1;
+        let suffix_width = if remaining > 0 {
+            format!(" +{} more", remaining).len()
+        } else {
+            0
+        };
+        let candidate_width = joined.chars().count() + separator.len() + item.chars().count();
+        if candidate_width + suffix_width > width && i > 0 {
+            return format!("{} +{} more", joined, items.len() - i);
+        }
+        joined.push_str(separator);
+        joined.push_str(item);
+    }
+    joined
+}
+
+/// List the partitions with the filtered one first and highlighted
+fn format_partitions(partitions: &[String], selected: Option<&str>, width: usize) -> String {
+    let mut ordered = partitions.to_vec();
+    if let Some(selected) = selected {
+        ordered.sort_by_key(|p| p != selected);
+    }
+    let formatted = truncate_list(&ordered, width);
+    match selected {
+        Some(selected) if ordered.first().is_some_and(|p| p == selected) => {
+            format!("{}{}", selected.bold(), &formatted[selected.len()..])
+        }
+        _ => formatted,
+    }
+}
+
+/// Reservation names, with maintenance reservations in yellow
+fn format_reservations(reservations: &[NodeReservation]) -> String {
+    reservations
         .iter()
-        .filter(|&node| {
-            let mut gres_matched = node.gres.contains(&cli.gres);
-            if let Some(ref partition) = cli.partition {
-                gres_matched &= node.partitions.contains(partition)
+        .map(|r| {
+            if r.maintenance {
+                r.name.yellow().to_string()
+            } else {
+                r.name.clone()
             }
-            gres_matched
         })
-        .map(TableNode::from_node)
-        .collect();
-    let tabled_nodes = matched?;
-    let mut table = Table::new(tabled_nodes);
-    apply_style_to_table(cli.style, &mut table);
-    println!("{}", table);
-    Ok(())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Deduplicated and sorted features, also splitting comma-joined entries
+fn normalize_features(features: &[String]) -> Vec<String> {
+    features
+        .iter()
+        .flat_map(|f| f.split(','))
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .collect::<BTreeSet<&str>>()
+        .into_iter()
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Abbreviation and color of the base states and flags that Slurm can report
+/// for a node, most important first: the first of a node's states decides the
+/// color of the state column. States that keep a node from running jobs come
+/// before the base states, which come before informational flags.
+const STATE_STYLES: [(&str, &str, Option<Color>); 26] = [
+    ("DOWN", "DOWN", Some(Color::Red)),
+    ("FAIL", "FAIL", Some(Color::Red)),
+    ("INVAL", "INVAL", Some(Color::Red)),
+    ("NOT_RESPONDING", "RESP?", Some(Color::Red)),
+    ("DRAINED", "DRND", Some(Color::Yellow)),
+    ("DRAIN", "DRN", Some(Color::Yellow)),
+    ("DRAINING", "DRNG", Some(Color::Yellow)),
+    ("REBOOT_ISSUED", "RBT", Some(Color::BrightYellow)),
+    ("REBOOT_REQUESTED", "RBT?", Some(Color::BrightYellow)),
+    ("POWERED_DOWN", "OFF", Some(Color::Cyan)),
+    ("POWERING_UP", "BOOT", Some(Color::BrightCyan)),
+    ("POWERING_DOWN", "PWDN", Some(Color::Cyan)),
+    ("POWER_DOWN", "PWDN?", Some(Color::Cyan)),
+    ("FUTURE", "FUT", Some(Color::BrightBlack)),
+    ("DYNAMIC_FUTURE", "DFUT", Some(Color::BrightBlack)),
+    ("UNKNOWN", "UNK", Some(Color::BrightBlack)),
+    ("ALLOCATED", "ALLOC", Some(Color::Magenta)),
+    ("MIXED", "MIX", Some(Color::Blue)),
+    ("COMPLETING", "COMP", Some(Color::BrightMagenta)),
+    ("IDLE", "IDLE", Some(Color::Green)),
+    ("BLOCKED", "BLOCK", Some(Color::BrightMagenta)),
+    ("MAINTENANCE", "MAINT", Some(Color::BrightYellow)),
+    ("RESERVED", "RESV", Some(Color::BrightBlue)),
+    ("PLANNED", "PLND", Some(Color::BrightBlue)),
+    ("CLOUD", "CLOUD", None),
+    ("DYNAMIC_NORM", "DNORM", None),
+];
+
+/// Position of a state in `STATE_STYLES`, with unknown states last
+fn state_rank(state: &str) -> usize {
+    STATE_STYLES
+        .iter()
+        .position(|&(name, _, _)| name == state)
+        .unwrap_or(STATE_STYLES.len())
+}
+
+/// The most important of the states, or the first one if none is known
+fn dominant_state(states: &[String]) -> Option<&str> {
+    // `min_by_key` keeps the first of equally ranked states
+    states
+        .iter()
+        .min_by_key(|state| state_rank(state))
+        .map(String::as_str)
+}
+
+/// Abbreviation and color of a state, unknown states are kept as they are
+fn state_style(state: &str) -> (&str, Option<Color>) {
+    STATE_STYLES
+        .iter()
+        .find(|&&(name, _, _)| name == state)
+        .map_or((state, None), |&(_, short, color)| (short, color))
+}
+
+/// Sets of icons prefixing states and GRES
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum IconSet {
+    /// Symbols from a Nerd Font patched font
+    Nerd,
+    /// Plain emoji, for terminals without a Nerd Font
+    Emoji,
+}
+
+/// Nerd Font and emoji icons of the main states
+const STATE_ICONS: [(&str, &str, &str); 7] = [
+    ("IDLE", "\u{f00c}", "✅"),
+    ("MIXED", "\u{f042}", "🟠"),
+    ("ALLOCATED", "\u{f111}", "🔴"),
+    ("DRAIN", "\u{f071}", "🚧"),
+    ("DOWN", "\u{f00d}", "❌"),
+    ("POWERED_DOWN", "\u{f011}", "💤"),
+    ("POWERING_UP", "\u{f0e7}", "⚡"),
+];
+
+/// Nerd Font icon of an expansion card, prefixing the GRES model
+const NERD_GRES_ICON: &str = "\u{f08ae}";
+
+fn state_icon(state: &str, icons: IconSet) -> Option<&'static str> {
+    STATE_ICONS
+        .iter()
+        .find(|&&(name, _, _)| name == state)
+        .map(|&(_, nerd, emoji)| match icons {
+            IconSet::Nerd => nerd,
+            IconSet::Emoji => emoji,
+        })
+}
+
+/// The dominant state as a colored badge, followed by the other states dimmed,
+/// e.g. `DRAIN IDLE`, or `DRN+IDLE` when abbreviated
+fn format_state(
+    states: &[String],
+    short: bool,
+    icons: Option<IconSet>,
+    palette: Palette,
+) -> String {
+    let Some(dominant) = dominant_state(states) else {
+        return "-".to_owned();
+    };
+    if let Some(icon) = icons.and_then(|icons| state_icon(dominant, icons)) {
+        return format!("{} {}", icon, format_state(states, short, None, palette));
+    }
+    let name = |state: &str| -> String {
+        if short {
+            state_style(state).0.to_owned()
+        } else {
+            state.to_owned()
+        }
+    };
+    let badge = match state_style(dominant).1 {
+        Some(color) => name(dominant).color(palette.color(color)),
+        None => name(dominant).normal(),
+    };
+    let secondary = states
+        .iter()
+        .filter(|s| *s != dominant)
+        .map(|s| name(s))
+        .collect::<Vec<String>>();
+    match (secondary.is_empty(), short) {
+        (true, _) => badge.to_string(),
+        (false, true) => format!("{}{}", badge, format!("+{}", secondary.join("+")).dimmed()),
+        (false, false) => format!("{} {}", badge, secondary.join(",").dimmed()),
+    }
+}
+
+fn format_load(load: Option<f64>, cpus: usize) -> String {
+    match load {
+        Some(load) => {
+            let formatted = format!("{:.1}", load);
+            if load > cpus as f64 {
+                formatted.red().to_string()
+            } else {
+                formatted
+            }
+        }
+        None => "-".to_owned(),
+    }
+}
+
+/// Resource status of a node, independent of how it is displayed
+#[derive(Serialize)]
+struct NodeStatus {
+    hostname: String,
+    state: Vec<String>,
+    partitions: Vec<String>,
+    cpus: usize,
+    idle_cpus: usize,
+    /// Memory in MB that jobs can use, excluding that reserved by `MemSpecLimit`
+    memory: usize,
+    /// Unallocated memory in MB
+    free_memory: usize,
+    gres: String,
+    /// GPU shards on a node listed by GPUs, which use the GPUs without allocating them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shards: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shards_used: Option<usize>,
+    /// The `gres` and `gres_used` strings as reported by Slurm
+    gres_raw: String,
+    gres_used_raw: String,
+    gres_total: usize,
+    /// Used GRES, including those held by preemptible and suspended jobs
+    gres_used: usize,
+    /// Used GRES held by suspended jobs, which are neither idle nor freed by preemption
+    gres_suspended: usize,
+    gres_preempted: usize,
+    gres_idle: usize,
+    /// Unallocated GRES drained individually, which are not counted as idle
+    gres_drained: usize,
+    /// Unallocated GRES on a node that cannot run jobs, which are not counted as idle
+    gres_offline: usize,
+    /// Unallocated GRES on a node that is powered down by power saving or still booting,
+    /// which are not counted as idle
+    gres_powered_down: usize,
+    /// Idle GRES plus those that could be freed by preempting jobs, which only
+    /// become available on nodes that can run new jobs
+    effective_free: usize,
+    /// Indices of the idle GRES, unknown if Slurm did not report used indices
+    /// or the node has several models
+    idle_indices: Option<IndexSet>,
+    /// Counts of each GRES model on the node
+    models: Vec<ModelStatus>,
+    features: Vec<String>,
+    cpu_load: Option<f64>,
+    /// Unix time of the last boot, unknown for nodes that are down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    boot_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slurmd_start_time: Option<u64>,
+    slurmd_version: String,
+    weight: u64,
+    tres: String,
+    tres_used: String,
+    boards: usize,
+    sockets: usize,
+    cores_per_socket: usize,
+    threads_per_core: usize,
+    /// Network address, if it differs from the hostname
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    /// Host name reported by slurmd, if it differs from the hostname
+    #[serde(skip_serializing_if = "Option::is_none")]
+    node_hostname: Option<String>,
+    /// Unix time when the first GPU job on a fully used node is expected to end
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_free_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    running_jobs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    users: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reservations: Option<Vec<NodeReservation>>,
+    /// Why the node was drained or set down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason_set_by_user: Option<String>,
+    /// Change of idle GRES since the snapshot given to `--diff`, unknown for new nodes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_change: Option<i64>,
+}
+
+/// GRES counts of a single model on a node
+#[derive(Serialize, Debug, PartialEq)]
+struct ModelStatus {
+    model: String,
+    total: usize,
+    /// Used GRES, including those held by preemptible and suspended jobs
+    used: usize,
+    /// Used GRES held by suspended jobs, which cannot be used until they resume
+    suspended: usize,
+    preempted: usize,
+    idle: usize,
+    /// Unallocated GRES drained individually with `gres_drain`
+    drained: usize,
+    /// Unallocated GRES on a node that cannot run jobs
+    offline: usize,
+    /// Unallocated GRES on a node that is powered down or booting
+    powered_down: usize,
+    idle_indices: Option<IndexSet>,
+    /// Sockets the GRES are attached to, if Slurm reports them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sockets: Option<IndexSet>,
+}
+
+impl ModelStatus {
+    /// Match the used and drained GRES to the configured ones by model
+    fn from_gres(total: &[GresStatus], used: &[GresStatus], drain: &[GresStatus]) -> Vec<Self> {
+        total
+            .iter()
+            .map(|total| {
+                let used = used.iter().find(|used| used.model == total.model);
+                let drain = drain.iter().find(|drain| drain.model == total.model);
+                let used_count = used.map_or(0, |used| used.count);
+                let unallocated = total.count.saturating_sub(used_count);
+                let mut idle_indices = used
+                    .and_then(|used| used.indices.as_ref())
+                    .map(|indices| indices.complement(total.count as u32));
+                // Only drained GRES that are not in use reduce the idle count
+                let drained = match (drain, &mut idle_indices) {
+                    (
+                        Some(GresStatus {
+                            indices: Some(drain_indices),
+                            ..
+                        }),
+                        Some(idle),
+                    ) => {
+                        let before = idle.count();
+                        *idle = idle.difference(drain_indices);
+                        // Used indices that are unknown or out of range leave more
+                        // indices idle than unallocated GRES
+                        (before - idle.count()).min(unallocated)
+                    }
+                    (Some(drain), _) => drain.count.min(unallocated),
+                    (None, _) => 0,
+                };
+                Self {
+                    model: total.model.clone(),
+                    total: total.count,
+                    used: used_count,
+                    suspended: 0,
+                    preempted: 0,
+                    idle: unallocated - drained,
+                    drained,
+                    offline: 0,
+                    powered_down: 0,
+                    idle_indices,
+                    sockets: total.sockets.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Used GRES that are not held by preemptible or suspended jobs
+    fn used_by_others(&self) -> usize {
+        self.used - self.preempted - self.suspended
+    }
+
+    /// Used, suspended, preempted, idle, drained, offline and powered down GRES
+    fn counts(&self) -> [usize; CATEGORIES] {
+        [
+            self.used_by_others(),
+            self.suspended,
+            self.preempted,
+            self.idle,
+            self.drained,
+            self.offline,
+            self.powered_down,
+        ]
+    }
+}
+
+/// Attribute GPUs held by jobs to the `category` of the node's models, counting
+/// any of an unknown model towards the first models with unaccounted used GPUs.
+/// Returns the number of GPUs beyond the used ones, which are not attributed.
+fn attribute_held(
+    models: &mut [ModelStatus],
+    held: &HashMap<String, usize>,
+    category: fn(&mut ModelStatus) -> &mut usize,
+) -> usize {
+    let mut unmatched = 0;
+    for (model, &count) in held {
+        match models.iter_mut().find(|status| &status.model == model) {
+            Some(status) => {
+                let attributed = count.min(status.used_by_others());
+                *category(status) += attributed;
+                unmatched += count - attributed;
+            }
+            None => unmatched += count,
+        }
+    }
+    for status in models.iter_mut() {
+        let attributed = unmatched.min(status.used_by_others());
+        *category(status) += attributed;
+        unmatched -= attributed;
+    }
+    unmatched
+}
+
+/// Whether a node cannot run new jobs because it is down, failed or drained.
+/// A draining node still has jobs running, which is reported as `DRAIN` with
+/// `MIXED` or `ALLOCATED`, and its unallocated GRES are counted as idle.
+fn is_offline(states: &[String]) -> bool {
+    let has = |state: &str| states.iter().any(|s| s == state);
+    has("DOWN")
+        || has("FAIL")
+        || has("DRAINED")
+        || (has("DRAIN") && !has("MIXED") && !has("ALLOCATED"))
+}
+
+/// Whether power saving has shut the node down, or it is shutting down or
+/// booting, so that its GRES are not immediately available
+fn is_powered_down(states: &[String]) -> bool {
+    states
+        .iter()
+        .any(|s| matches!(s.as_str(), "POWERED_DOWN" | "POWERING_UP" | "POWERING_DOWN"))
+}
+
+/// Check that the partition and state filters can match anything,
+/// so that a typo is reported instead of producing an empty table
+fn validate_filters(nodes: &[Node], partition: Option<&str>, states: &[String]) -> Result<()> {
+    if let Some(partition) = partition {
+        let available: BTreeSet<&str> = nodes
+            .iter()
+            .flat_map(|node| node.partitions.iter().map(String::as_str))
+            .collect();
+        if !available.contains(partition) {
+            bail!(
+                "Unknown partition \"{}\", available partitions: {}",
+                partition,
+                available.into_iter().collect::<Vec<&str>>().join(", ")
+            );
+        }
+    }
+    let mut known: BTreeSet<&str> = STATE_STYLES.iter().map(|&(name, _, _)| name).collect();
+    known.extend(
+        nodes
+            .iter()
+            .flat_map(|node| node.state.iter().map(String::as_str)),
+    );
+    for state in states {
+        if !known.contains(state.as_str()) {
+            bail!(
+                "Unknown state \"{}\", known states: {}",
+                state,
+                known.into_iter().collect::<Vec<&str>>().join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// How the positional GRES argument selects nodes
+enum GresFilter {
+    /// Lowercase part of the model, matched regardless of case
+    Substring(String),
+    /// Regex from an alias, e.g. `a100` standing for `a100-sxm4-.*`
+    Alias(Regex),
+}
+
+impl GresFilter {
+    /// Resolve each of the comma-separated `names` through the alias file, if any,
+    /// falling back to a substring match
+    fn parse_list(names: &str, alias_file: Option<&std::path::Path>) -> Result<Vec<Self>> {
+        let aliases: HashMap<String, String> = match alias_file {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Reading alias file {} failed", path.display()))?;
+                serde_json::from_str(&content)
+                    .with_context(|| format!("Parsing alias file {} failed", path.display()))?
+            }
+            None => HashMap::new(),
+        };
+        // Empty names would match every node
+        let filters = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| match aliases.get(name) {
+                Some(pattern) => Regex::new(pattern)
+                    .map(Self::Alias)
+                    .with_context(|| format!("Invalid pattern for alias \"{}\"", name)),
+                // Vendors capitalize models differently, e.g. `MI250X` or `a100`
+                None => Ok(Self::Substring(name.to_lowercase())),
+            })
+            .collect::<Result<Vec<Self>>>()?;
+        if filters.is_empty() {
+            bail!("No GRES name given in \"{}\"", names);
+        }
+        Ok(filters)
+    }
+
+    fn matches(&self, gres: &str) -> bool {
+        match self {
+            Self::Substring(name) => gres.to_lowercase().contains(name.as_str()),
+            Self::Alias(regex) => regex.is_match(gres),
+        }
+    }
+}
+
+/// Key-value pairs of a TRES string such as `cpu=32,mem=200000M,gres/gpu=4`
+fn parse_tres(tres: &str) -> impl Iterator<Item = (&str, &str)> {
+    tres.split(',').filter_map(|entry| entry.split_once('='))
+}
+
+/// The total count of a GRES type in a TRES string, e.g. 4 for `gres/gpu=4`,
+/// ignoring per-model entries
+fn tres_gres_count(tres: &str, gres_type: &str) -> Option<usize> {
+    parse_tres(tres)
+        .find(|&(key, _)| key.strip_prefix("gres/") == Some(gres_type))
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// Shorten a memory amount in megabytes such as `200000M` to `195G`
+fn shorten_memory(value: &str) -> String {
+    match value
+        .strip_suffix('M')
+        .and_then(|mb| mb.parse::<usize>().ok())
+    {
+        Some(mb) if mb >= 1024 => format!("{}G", mb / 1024),
+        _ => value.to_owned(),
+    }
+}
+
+/// Abbreviate a TRES string by dropping `gres/` prefixes and shortening memory
+fn abbreviate_tres(tres: &str) -> String {
+    parse_tres(tres)
+        .map(|(key, value)| {
+            let key = key.strip_prefix("gres/").unwrap_or(key);
+            let value = if key == "mem" {
+                shorten_memory(value)
+            } else {
+                value.to_owned()
+            };
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// A timestamp, treating zero as unknown
+fn nonzero_time(time: Option<SlurmNumber>) -> Option<u64> {
+    time.and_then(SlurmNumber::value).filter(|&t| t > 0)
+}
+
+/// `name` unless it is empty or the same as `hostname`
+fn distinct_name(name: &str, hostname: &str) -> Option<String> {
+    (!name.is_empty() && name != hostname).then(|| name.to_owned())
+}
+
+/// Nodes already warned about for reporting more allocated memory than they have
+static OVERALLOCATED_NODES: Lazy<Mutex<BTreeSet<String>>> =
+    Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+impl NodeStatus {
+    /// Used, suspended, preempted, idle, drained, offline and powered down GRES
+    fn gres_counts(&self) -> [usize; CATEGORIES] {
+        [
+            self.gres_used - self.gres_preempted - self.gres_suspended,
+            self.gres_suspended,
+            self.gres_preempted,
+            self.gres_idle,
+            self.gres_drained,
+            self.gres_offline,
+            self.gres_powered_down,
+        ]
+    }
+
+    /// Disagreements of the configured and used GRES counts with those in TRES,
+    /// which point at parsing bugs or inconsistent accounting in Slurm
+    fn tres_mismatches(&self, gres_type: &str) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if let Some(tres_total) = tres_gres_count(&self.tres, gres_type) {
+            if tres_total != self.gres_total {
+                mismatches.push(format!(
+                    "{} reports {} {} in tres but {} in gres",
+                    self.hostname, tres_total, gres_type, self.gres_total
+                ));
+            }
+        }
+        if let Some(tres_used) = tres_gres_count(&self.tres_used, gres_type) {
+            if tres_used != self.gres_used {
+                mismatches.push(format!(
+                    "{} reports {} used {} in tres_used but {} in gres_used",
+                    self.hostname, tres_used, gres_type, self.gres_used
+                ));
+            }
+        }
+        for model in &self.models {
+            let key = format!("gres/{}", model.model);
+            let count = |tres: &str| {
+                parse_tres(tres)
+                    .find(|&(k, _)| k == key)
+                    .and_then(|(_, value)| value.parse::<usize>().ok())
+            };
+            if let Some(total) = count(&self.tres).filter(|&total| total != model.total) {
+                mismatches.push(format!(
+                    "{} reports {} {} in tres but {} in gres",
+                    self.hostname, total, model.model, model.total
+                ));
+            }
+            if let Some(used) = count(&self.tres_used).filter(|&used| used != model.used) {
+                mismatches.push(format!(
+                    "{} reports {} used {} in tres_used but {} in gres_used",
+                    self.hostname, used, model.model, model.used
+                ));
+            }
+        }
+        mismatches
+    }
+
+    /// Idle GRES of the models that match any of the filters, `None` if none does
+    fn idle_matching(&self, gres_filters: &[GresFilter]) -> Option<usize> {
+        let mut matching = self
+            .models
+            .iter()
+            .filter(|model| {
+                gres_filters
+                    .iter()
+                    .any(|filter| filter.matches(&model.model))
+            })
+            .peekable();
+        matching.peek()?;
+        Some(matching.map(|model| model.idle).sum())
+    }
+
+    /// Idle GRES, including those of a powered down node if `count_powered_down` is set
+    fn available_gres(&self, count_powered_down: bool) -> usize {
+        if count_powered_down {
+            self.gres_idle + self.gres_powered_down
+        } else {
+            self.gres_idle
+        }
+    }
+
+    fn is_reserved(&self) -> bool {
+        self.reservations
+            .as_ref()
+            .is_some_and(|reservations| !reservations.is_empty())
+    }
+
+    /// Status of the node's GRES of `gres_type`
+    fn from_node(
+        node: &Node,
+        gres_type: &str,
+        jobs: Option<&JobSummary>,
+        reservations: Option<&ReservationSummary>,
+    ) -> Result<Self> {
+        let mut total = GresStatus::parse_list(&node.gres, gres_type);
+        let mut used = GresStatus::parse_list(&node.gres_used, gres_type);
+        if total.is_empty() {
+            // Some configurations only track the GRES in TRES, without models
+            if let Some(count) = tres_gres_count(&node.tres, gres_type) {
+                debug!(
+                    "{} has no {} in gres, counting them from tres",
+                    node.hostname, gres_type
+                );
+                let used_count = tres_gres_count(&node.tres_used, gres_type).unwrap_or(0);
+                total = vec![GresStatus::new(gres_type, count)];
+                used = vec![GresStatus::new(gres_type, used_count)];
+            }
+        }
+        let mut models = ModelStatus::from_gres(
+            &total,
+            &used,
+            &GresStatus::parse_list(&node.gres_drain, gres_type),
+        );
+        // Shards of a GPU are allocated without allocating the GPU itself
+        let shards = match gres_type {
+            GPU_TYPE => {
+                let total = GresStatus::parse_list(&node.gres, SHARD_TYPE);
+                let used = GresStatus::parse_list(&node.gres_used, SHARD_TYPE);
+                let count = |statuses: &[GresStatus]| statuses.iter().map(|s| s.count).sum();
+                Some((count(&used), count(&total))).filter(|&(_, total)| total > 0)
+            }
+            _ => None,
+        };
+        // Suspended jobs keep their GRES allocated, so they count as used but not free
+        if let Some(suspended) = jobs.and_then(|jobs| jobs.suspended_gpus.get(&node.hostname)) {
+            let excess = attribute_held(&mut models, suspended, |model| &mut model.suspended);
+            if excess > 0 {
+                debug!(
+                    "{} has {} more {} in suspended jobs than in use",
+                    node.hostname, excess, gres_type
+                );
+            }
+        }
+        if let Some(preempted) = jobs.and_then(|jobs| jobs.preempted_gpus.get(&node.hostname)) {
+            let excess = attribute_held(&mut models, preempted, |model| &mut model.preempted);
+            if excess > 0 {
+                debug!(
+                    "{} has {} more {} in preemptible jobs than in use",
+                    node.hostname, excess, gres_type
+                );
+            }
+        }
+        // Stale accounting can report more allocated memory than the node has,
+        // warned about once per node rather than on every refresh
+        if node.alloc_memory > node.real_memory
+            && OVERALLOCATED_NODES
+                .lock()
+                .unwrap()
+                .insert(node.hostname.clone())
+        {
+            eprintln!(
+                "Warning: {} reports {} MB allocated but only {} MB of memory",
+                node.hostname, node.alloc_memory, node.real_memory
+            );
+        }
+        let memory = node.real_memory.saturating_sub(node.mem_spec_limit);
+        let offline = is_offline(&node.state);
+        let powered_down = is_powered_down(&node.state);
+        if offline {
+            for model in models.iter_mut() {
+                model.offline = std::mem::take(&mut model.idle);
+            }
+        } else if powered_down {
+            for model in models.iter_mut() {
+                model.powered_down = std::mem::take(&mut model.idle);
+            }
+        }
+        let gres_total = models.iter().map(|model| model.total).sum();
+        let gres_used = models.iter().map(|model| model.used).sum();
+        let gres_suspended = models.iter().map(|model| model.suspended).sum();
+        let gres_preempted = models.iter().map(|model| model.preempted).sum();
+        let gres_idle = models.iter().map(|model| model.idle).sum();
+        let gres_drained = models.iter().map(|model| model.drained).sum();
+        let gres_offline = models.iter().map(|model| model.offline).sum();
+        let gres_powered_down = models.iter().map(|model| model.powered_down).sum();
+        Ok(Self {
+            hostname: node.hostname.clone(),
+            state: node.state.clone(),
+            partitions: node.partitions.clone(),
+            cpus: node.cpus,
+            idle_cpus: node.alloc_idle_cpus,
+            memory,
+            free_memory: memory.saturating_sub(node.alloc_memory),
+            gres: models
+                .iter()
+                .map(|model| model.model.as_str())
+                .collect::<Vec<&str>>()
+                .join(","),
+            shards_used: shards.map(|(used, _)| used),
+            shards: shards.map(|(_, total)| total),
+            gres_raw: node.gres.clone(),
+            gres_used_raw: node.gres_used.clone(),
+            gres_total,
+            gres_used,
+            gres_suspended,
+            gres_preempted,
+            gres_idle,
+            gres_drained,
+            gres_offline,
+            gres_powered_down,
+            effective_free: if offline || powered_down {
+                gres_idle
+            } else {
+                gres_idle + gres_preempted
+            },
+            idle_indices: match models.as_slice() {
+                [model] => model.idle_indices.clone(),
+                _ => None,
+            },
+            models,
+            features: normalize_features(&node.active_features),
+            cpu_load: node
+                .cpu_load
+                .and_then(SlurmNumber::value)
+                .map(|scaled| scaled as f64 / 100.0),
+            boot_time: nonzero_time(node.boot_time),
+            slurmd_start_time: nonzero_time(node.slurmd_start_time),
+            slurmd_version: node.version.clone(),
+            // Slurm defaults the weight to 1 when it is not configured
+            weight: node.weight.and_then(SlurmNumber::value).unwrap_or(1),
+            tres: node.tres.clone(),
+            tres_used: node.tres_used.clone(),
+            boards: node.boards,
+            sockets: node.sockets,
+            cores_per_socket: node.cores,
+            threads_per_core: node.threads,
+            address: distinct_name(&node.address, &node.hostname),
+            node_hostname: distinct_name(&node.node_hostname, &node.hostname),
+            next_free_at: jobs
+                .and_then(|jobs| jobs.next_gpu_release.get(&node.hostname).copied())
+                .flatten(),
+            running_jobs: jobs
+                .map(|jobs| jobs.running_jobs.get(&node.hostname).copied().unwrap_or(0)),
+            users: jobs.map(|jobs| {
+                jobs.users
+                    .get(&node.hostname)
+                    .map(|users| users.iter().cloned().collect())
+                    .unwrap_or_default()
+            }),
+            reservations: reservations.map(|reservations| {
+                reservations
+                    .nodes
+                    .get(&node.hostname)
+                    .cloned()
+                    .unwrap_or_default()
+            }),
+            reason: Some(node.reason.clone()).filter(|reason| !reason.is_empty()),
+            reason_set_by_user: Some(node.reason_set_by_user.clone())
+                .filter(|user| !user.is_empty()),
+            idle_change: None,
+        })
+    }
+}
+
+/// Aggregated GRES counts across the listed nodes
+#[derive(Default)]
+struct GresSummary {
+    idle: usize,
+    total: usize,
+    /// GRES of powered down nodes counted as idle, available after they boot
+    after_boot: usize,
+    /// Idle and total counts keyed by the full model, e.g. `gpu:a100`
+    models: BTreeMap<String, (usize, usize)>,
+}
+
+impl GresSummary {
+    /// Sum the GRES of the nodes, counting those on powered down nodes as idle
+    /// if `count_powered_down` is set
+    fn from_statuses(statuses: &[NodeStatus], count_powered_down: bool) -> Self {
+        let mut summary = Self::default();
+        for node in statuses {
+            summary.total += node.gres_total;
+            for model in &node.models {
+                let after_boot = if count_powered_down {
+                    model.powered_down
+                } else {
+                    0
+                };
+                summary.idle += model.idle + after_boot;
+                summary.after_boot += after_boot;
+                let (idle, total) = summary.models.entry(model.model.clone()).or_default();
+                *idle += model.idle + after_boot;
+                *total += model.total;
+            }
+        }
+        summary
+    }
+
+    /// A single plain line such as `Idle GPUs: 37/512 (a100:20, h100:17)`
+    fn one_line(&self) -> String {
+        let all_gpus = self
+            .models
+            .keys()
+            .all(|model| model == "gpu" || model.starts_with("gpu:"));
+        let any_mig = self.models.keys().any(|model| is_mig_model(model));
+        // MIG slices and whole GPUs add up to a count of devices, not of GPUs
+        let label = match (all_gpus, any_mig) {
+            (true, false) => "GPUs",
+            (true, true) => "GPUs and MIG slices",
+            (false, _) => "GRES",
+        };
+        let models = self
+            .models
+            .iter()
+            .map(|(model, (idle, _))| format!("{}:{}", model_name(model), idle))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let line = format!("Idle {}: {}/{} ({})", label, self.idle, self.total, models);
+        if self.after_boot > 0 {
+            format!("{}, {} after boot", line, self.after_boot)
+        } else {
+            line
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct TableNode {
+    hostname: String,
+    cpus_available: String,
+    memory_available: String,
+    gres: String,
+    gres_status: String,
+    state: String,
+    state_full: String,
+    features: String,
+    load: String,
+    jobs: String,
+    users: String,
+    partitions: String,
+    reserved: String,
+    reservation: String,
+    gpu_util: String,
+    effective_free: String,
+    next_free: String,
+    idle_idx: String,
+    address: String,
+    uptime: String,
+    slurmd_version: String,
+    tres_used: String,
+    weight: String,
+    reason: String,
+    idle_change: String,
+    topology: String,
+    tres: String,
+    gres_raw: String,
+    gres_used_raw: String,
+}
+
+/// Characters and colors used for each category in the gres_status column
+struct Glyphs {
+    used: char,
+    suspended: char,
+    preempted: char,
+    idle: char,
+    drained: char,
+    offline: char,
+    powered_down: char,
+    used_color: Color,
+    suspended_color: Color,
+    preempted_color: Color,
+    idle_color: Color,
+    drained_color: Color,
+    offline_color: Color,
+    powered_down_color: Color,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Self {
+            used: 'u',
+            suspended: 's',
+            preempted: 'p',
+            idle: 'i',
+            drained: 'd',
+            offline: 'o',
+            powered_down: 'z',
+            used_color: Color::Red,
+            suspended_color: Color::Cyan,
+            preempted_color: Color::Yellow,
+            idle_color: Color::Green,
+            drained_color: Color::Magenta,
+            offline_color: Color::BrightBlack,
+            powered_down_color: Color::Blue,
+        }
+    }
+}
+
+impl Glyphs {
+    /// Combine the command line, the config file and the defaults, in that order
+    fn resolve(cli: &Cli, config: &Config) -> Result<Self> {
+        let default = Self::default();
+        let glyph = |cli: Option<char>, config: &Option<String>, default: char| match (cli, config)
+        {
+            (Some(glyph), _) => Ok(glyph),
+            (None, Some(glyph)) => parse_glyph(glyph),
+            (None, None) => Ok(default),
+        };
+        let palette = cli.palette(config)?;
+        let color = |cli: &Option<String>, config: &Option<String>, default: Color| match cli
+            .as_ref()
+            .or(config.as_ref())
+        {
+            Some(color) => parse_color(color).map(|color| Color::from(color.as_str())),
+            None => Ok(palette.color(default)),
+        };
+        Ok(Self {
+            used: glyph(cli.glyph_used, &config.glyph_used, default.used)?,
+            suspended: glyph(
+                cli.glyph_suspended,
+                &config.glyph_suspended,
+                default.suspended,
+            )?,
+            preempted: glyph(
+                cli.glyph_preempted,
+                &config.glyph_preempted,
+                default.preempted,
+            )?,
+            idle: glyph(cli.glyph_idle, &config.glyph_idle, default.idle)?,
+            drained: glyph(cli.glyph_drained, &config.glyph_drained, default.drained)?,
+            offline: glyph(cli.glyph_offline, &config.glyph_offline, default.offline)?,
+            powered_down: glyph(
+                cli.glyph_powered_down,
+                &config.glyph_powered_down,
+                default.powered_down,
+            )?,
+            used_color: color(&cli.color_used, &config.color_used, default.used_color)?,
+            suspended_color: color(
+                &cli.color_suspended,
+                &config.color_suspended,
+                default.suspended_color,
+            )?,
+            preempted_color: color(
+                &cli.color_preempted,
+                &config.color_preempted,
+                default.preempted_color,
+            )?,
+            idle_color: color(&cli.color_idle, &config.color_idle, default.idle_color)?,
+            drained_color: color(
+                &cli.color_drained,
+                &config.color_drained,
+                default.drained_color,
+            )?,
+            offline_color: color(
+                &cli.color_offline,
+                &config.color_offline,
+                default.offline_color,
+            )?,
+            powered_down_color: color(
+                &cli.color_powered_down,
+                &config.color_powered_down,
+                default.powered_down_color,
+            )?,
+        })
+    }
+
+    /// Characters and colors of the used, suspended, preempted, idle, drained,
+    /// offline and powered down GRES
+    fn categories(&self) -> [(char, Color); CATEGORIES] {
+        [
+            (self.used, self.used_color),
+            (self.suspended, self.suspended_color),
+            (self.preempted, self.preempted_color),
+            (self.idle, self.idle_color),
+            (self.drained, self.drained_color),
+            (self.offline, self.offline_color),
+            (self.powered_down, self.powered_down_color),
+        ]
+    }
+}
+
+/// Accept a single character that occupies exactly one terminal column
+fn parse_glyph(s: &str) -> Result<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.width() == Some(1) => Ok(c),
+        _ => bail!("Glyph must be a single character of display width 1"),
+    }
+}
+
+/// Accept a color name known to `colored`, e.g. "blue" or "bright cyan"
+fn parse_color(s: &str) -> Result<String> {
+    match s.parse::<colored::Color>() {
+        Ok(_) => Ok(s.to_owned()),
+        Err(()) => bail!("Unknown color \"{}\"", s),
+    }
+}
+
+struct DisplayOptions {
+    /// Unix time of the query, for relative durations
+    now: u64,
+    palette: Palette,
+    /// Prefix removed from the hostname column
+    hostname_prefix: Option<String>,
+    list_width: usize,
+    partition: Option<String>,
+    exclude_preempted: bool,
+    glyphs: Glyphs,
+    /// Number of GRES per glyph, chosen per node if not fixed
+    glyph_scale: Option<usize>,
+    /// Width of the bar replacing the glyphs in the gres_status column
+    bar_width: Option<usize>,
+    ascii_glyphs: bool,
+    short_state: bool,
+    ratio: Ratio,
+    /// Show memory as a percentage of the total instead of in GB
+    mem_percent: bool,
+    /// Decimals of memory amounts in GB
+    mem_precision: usize,
+    /// Spell out the gres_status counts for screen readers
+    accessible: bool,
+    icons: Option<IconSet>,
+}
+
+impl DisplayOptions {
+    /// A line explaining the characters of the gres_status column as rendered
+    fn legend(&self) -> String {
+        let categories = self.glyphs.categories();
+        let characters = match self.bar_width {
+            Some(_) => bar_chars(self.ascii_glyphs),
+            None => categories.map(|(character, _)| character),
+        };
+        characters
+            .iter()
+            .zip(categories)
+            .zip([
+                "used",
+                "suspended",
+                "preempted",
+                "idle",
+                "drained",
+                "offline",
+                "powered down",
+            ])
+            .map(|((&character, (_, color)), label)| {
+                format!("{} {}", repeat_colored_char(character, 1, color), label)
+            })
+            .collect::<Vec<String>>()
+            .join("  ")
+    }
+}
+
+/// The gres_status column: glyphs per GRES, segmented by model on nodes with
+/// several, a fixed-width bar, or the counts spelled out for screen readers
+fn format_gres_cell(node: &NodeStatus, options: &DisplayOptions) -> String {
+    let glyphs = &options.glyphs;
+    match (options.accessible, options.bar_width) {
+        (true, _) => {
+            let [used, suspended, preempted, idle, drained, offline, powered_down] =
+                node.gres_counts();
+            let mut text = format!("{} used, {} preempted, {} idle", used, preempted, idle);
+            if suspended > 0 {
+                text += &format!(", {} suspended", suspended);
+            }
+            if drained > 0 {
+                text += &format!(", {} drained", drained);
+            }
+            if offline > 0 {
+                text += &format!(", {} offline", offline);
+            }
+            if powered_down > 0 {
+                text += &format!(", {} powered down", powered_down);
+            }
+            text
+        }
+        (false, Some(width)) => {
+            format_gres_bar(node.gres_counts(), width, options.ascii_glyphs, glyphs)
+        }
+        // Segment nodes with several models, e.g. `a40[uuii] a100[ui]`
+        (false, None) if node.models.len() > 1 => node
+            .models
+            .iter()
+            .map(|model| {
+                format!(
+                    "{}[{}]",
+                    model_name(&model.model),
+                    format_gres_status(model.counts(), options.glyph_scale, glyphs, true)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" "),
+        (false, None) => format_gres_status(node.gres_counts(), options.glyph_scale, glyphs, true),
+    }
+}
+
+impl TableNode {
+    fn from_status(node: &NodeStatus, options: &DisplayOptions) -> Self {
+        Self {
+            // Only the display is shortened, keeping whole names that equal the prefix
+            hostname: options
+                .hostname_prefix
+                .as_deref()
+                .and_then(|prefix| node.hostname.strip_prefix(prefix))
+                .filter(|rest| !rest.is_empty())
+                .unwrap_or(&node.hostname)
+                .to_owned(),
+            cpus_available: {
+                let used_cpus = node.cpus.saturating_sub(node.idle_cpus);
+                let ratio = match options.ratio {
+                    Ratio::Free => format_ratio(node.idle_cpus, node.cpus),
+                    Ratio::Used => format_ratio(used_cpus, node.cpus),
+                };
+                match usage_percent(used_cpus, node.cpus) {
+                    Some(percent) => color_by_usage(&ratio, percent, options.palette),
+                    None => ratio,
+                }
+            },
+            memory_available: {
+                let used_memory = node.memory.saturating_sub(node.free_memory);
+                let (shown, label) = match options.ratio {
+                    Ratio::Free => (node.free_memory, "free"),
+                    Ratio::Used => (used_memory, "used"),
+                };
+                match (
+                    options.mem_percent,
+                    rounded_percent(used_memory, node.memory),
+                ) {
+                    // Misconfigured nodes can report no memory at all
+                    (true, None) => "-".to_owned(),
+                    (true, Some(used_percent)) => {
+                        let percent = rounded_percent(shown, node.memory).unwrap_or_default();
+                        color_by_usage(
+                            &format!("{}% {}", percent, label),
+                            used_percent,
+                            options.palette,
+                        )
+                    }
+                    (false, _) => format!(
+                        "{}/{}G",
+                        format_memory(shown, options.mem_precision),
+                        format_memory(node.memory, options.mem_precision)
+                    ),
+                }
+            },
+            gres: {
+                let mut gres = node
+                    .models
+                    .iter()
+                    .map(|model| format!("{} ×{}", model.model, model.total))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                // GPUs that look idle may be partially used through shards
+                if let (Some(used), Some(total)) = (node.shards_used, node.shards) {
+                    if used > 0 {
+                        gres += &format!(" (shards {}/{} used)", used, total);
+                    }
+                }
+                match options.icons {
+                    Some(IconSet::Nerd) => format!("{} {}", NERD_GRES_ICON, gres),
+                    _ => gres,
+                }
+            },
+            gres_status: format_gres_cell(node, options),
+            state: format_state(
+                &node.state,
+                options.short_state,
+                options.icons,
+                options.palette,
+            ),
+            state_full: node.state.join(","),
+            features: truncate_with_ellipsis(&node.features.join(","), options.list_width),
+            load: format_load(node.cpu_load, node.cpus),
+            jobs: node.running_jobs.unwrap_or(0).to_string(),
+            users: truncate_list(
+                node.users.as_deref().unwrap_or_default(),
+                options.list_width,
+            ),
+            partitions: format_partitions(
+                &node.partitions,
+                options.partition.as_deref(),
+                options.list_width,
+            ),
+            reserved: if node.is_reserved() { "yes" } else { "no" }.to_owned(),
+            reservation: format_reservations(node.reservations.as_deref().unwrap_or_default()),
+            gpu_util: {
+                let used = if options.exclude_preempted {
+                    node.gres_used - node.gres_preempted
+                } else {
+                    node.gres_used
+                };
+                match usage_percent(used, node.gres_total) {
+                    Some(percent) => {
+                        color_by_usage(&format!("{}%", percent), percent, options.palette)
+                    }
+                    None => "-".to_owned(),
+                }
+            },
+            effective_free: node.effective_free.to_string(),
+            next_free: if node.gres_idle > 0 {
+                "now".to_owned()
+            } else {
+                match node.next_free_at {
+                    Some(at) => time::format_duration(at.saturating_sub(options.now)),
+                    None => "∞".to_owned(),
+                }
+            },
+            idle_idx: if node.models.len() > 1 {
+                node.models
+                    .iter()
+                    .map(|model| {
+                        let indices = model
+                            .idle_indices
+                            .as_ref()
+                            .map_or("?".to_owned(), IndexSet::to_string);
+                        format!("{}:{}", model_name(&model.model), indices)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            } else {
+                node.idle_indices
+                    .as_ref()
+                    .map_or("?".to_owned(), IndexSet::to_string)
+            },
+            address: match (&node.address, &node.node_hostname) {
+                (Some(address), Some(node_hostname)) if address != node_hostname => {
+                    format!("{} ({})", address, node_hostname)
+                }
+                (Some(address), _) => address.clone(),
+                (None, Some(node_hostname)) => node_hostname.clone(),
+                (None, None) => "-".to_owned(),
+            },
+            slurmd_version: node.slurmd_version.clone(),
+            tres_used: abbreviate_tres(&node.tres_used),
+            weight: node.weight.to_string(),
+            topology: format!(
+                "{}:{}:{}:{}",
+                node.boards, node.sockets, node.cores_per_socket, node.threads_per_core
+            ),
+            tres: abbreviate_tres(&node.tres),
+            gres_raw: node.gres_raw.clone(),
+            gres_used_raw: node.gres_used_raw.clone(),
+            idle_change: match node.idle_change {
+                Some(change) if change > 0 => format!("+{}", change)
+                    .color(options.palette.color(Color::Green))
+                    .to_string(),
+                Some(change) if change < 0 => change.to_string().red().to_string(),
+                Some(_) => "0".to_owned(),
+                None => "new".to_owned(),
+            },
+            reason: match (&node.reason, &node.reason_set_by_user) {
+                // Slurm may keep a stale reason on nodes that are back to idle
+                _ if node.state.iter().all(|s| s == "IDLE") => "-".to_owned(),
+                (Some(reason), Some(user)) => {
+                    truncate_with_ellipsis(&format!("{} ({})", reason, user), options.list_width)
+                }
+                (Some(reason), None) => truncate_with_ellipsis(reason, options.list_width),
+                (None, _) => "-".to_owned(),
+            },
+            uptime: match node.boot_time {
+                Some(boot_time) => {
+                    let uptime = options.now.saturating_sub(boot_time);
+                    let formatted = time::format_duration(uptime);
+                    if uptime < 3600 {
+                        formatted.yellow().to_string()
+                    } else {
+                        formatted
+                    }
+                }
+                None => "-".to_owned(),
+            },
+        }
+    }
+}
+
+const SCONTROL: &str = "scontrol";
+const SACCTMGR: &str = "sacctmgr";
+const NODES_ARGS: [&str; 3] = ["show", "nodes", "--json"];
+const JOBS_ARGS: [&str; 3] = ["show", "job", "--json"];
+const RESERVATIONS_ARGS: [&str; 3] = ["show", "reservations", "--json"];
+const PARTITIONS_ARGS: [&str; 3] = ["show", "partition", "--json"];
+const QOS_ARGS: [&str; 3] = ["show", "qos", "--json"];
+
+/// Program that runs a query: QOS are read from the accounting database with sacctmgr,
+/// everything else from the controller with scontrol
+fn query_program(args: &[&str]) -> &'static str {
+    if args == QOS_ARGS {
+        SACCTMGR
+    } else {
+        SCONTROL
+    }
+}
+
+struct ScontrolOptions {
+    print_commands: bool,
+    cluster: Option<String>,
+    /// How many times to retry after a transient failure
+    retries: u32,
+    /// Directory of saved scontrol output to read instead of running scontrol
+    input_dir: Option<std::path::PathBuf>,
+}
+
+/// File in `--input-dir` holding the output of the scontrol query with `args`
+fn input_file_name(args: &[&str]) -> Result<&'static str> {
+    if args == NODES_ARGS {
+        Ok("nodes.json")
+    } else if args == JOBS_ARGS {
+        Ok("jobs.json")
+    } else if args == RESERVATIONS_ARGS {
+        Ok("reservations.json")
+    } else if args == PARTITIONS_ARGS {
+        Ok("partitions.json")
+    } else if args == QOS_ARGS {
+        Ok("qos.json")
+    } else {
+        bail!(
+            "No input file for {} {}",
+            query_program(args),
+            args.join(" ")
+        )
+    }
+}
+
+/// Errors from an overloaded or briefly unreachable controller that are worth retrying
+const TRANSIENT_ERRORS: [&str; 4] = [
+    "Socket timed out",
+    "Unable to contact slurm controller",
+    "Connection refused",
+    "Resource temporarily unavailable",
+];
+
+fn is_transient_error(stderr: &str) -> bool {
+    TRANSIENT_ERRORS
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+}
+
+impl ScontrolOptions {
+    /// Full argument list for scontrol, including the cluster selection. QOS belong
+    /// to the accounting database rather than a cluster, so sacctmgr takes none.
+    fn args<'a>(&'a self, args: &[&'a str]) -> Vec<&'a str> {
+        let mut full_args = Vec::with_capacity(args.len() + 2);
+        if let Some(cluster) = self
+            .cluster
+            .as_ref()
+            .filter(|_| query_program(args) == SCONTROL)
+        {
+            full_args.extend(["-M", cluster.as_str()]);
+        }
+        full_args.extend(args);
+        full_args
+    }
+}
+
+fn format_scontrol_command(options: &ScontrolOptions, args: &[&str]) -> String {
+    std::iter::once(query_program(args))
+        .chain(options.args(args))
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn run_scontrol_command(options: &ScontrolOptions, args: &[&str]) -> Result<String> {
+    if options.print_commands {
+        eprintln!("{}", format_scontrol_command(options, args));
+    }
+    if let Some(ref dir) = options.input_dir {
+        let path = dir.join(input_file_name(args)?);
+        let content =
+            std::fs::read(&path).with_context(|| format!("Reading {} failed", path.display()))?;
+        return Ok(String::from_utf8_lossy(&content).into_owned());
+    }
+    let mut attempt = 0;
+    loop {
+        let start = std::time::Instant::now();
+        let output = std::process::Command::new(query_program(args))
+            .args(options.args(args))
+            .output()
+            .with_context(|| format!("Running {} failed", query_program(args)))?;
+        debug!(
+            "{} took {:.2?}",
+            format_scontrol_command(options, args),
+            start.elapsed()
+        );
+        // Free-form fields such as node reasons may contain invalid UTF-8
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+        }
+        let error_msg = String::from_utf8_lossy(&output.stderr).into_owned();
+        if attempt < options.retries && is_transient_error(&error_msg) {
+            // Back off exponentially from half a second
+            debug!("Retrying after a transient failure: {}", error_msg.trim());
+            std::thread::sleep(std::time::Duration::from_millis(500 << attempt));
+            attempt += 1;
+            continue;
+        }
+        match options.cluster {
+            Some(ref cluster) => bail!(
+                "{} failed on cluster {}: {}",
+                query_program(args),
+                cluster,
+                &error_msg
+            ),
+            None => bail!("{} failed: {}", query_program(args), &error_msg),
+        }
+    }
+}
+
+fn parse_state(state: &str) -> Result<String> {
+    Ok(state.trim().to_uppercase())
+}
+
+fn parse_gres_type(gres_type: &str) -> Result<String> {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\w+$").unwrap());
+    if RE.is_match(gres_type) {
+        Ok(gres_type.to_owned())
+    } else {
+        bail!("Invalid GRES type \"{}\"", gres_type)
+    }
+}
+
+fn parse_cluster_name(name: &str) -> Result<String> {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[\w.-]+$").unwrap());
+    if RE.is_match(name) {
+        Ok(name.to_owned())
+    } else {
+        bail!("Invalid cluster name \"{}\"", name)
+    }
+}
+
+fn query_nodes(options: &ScontrolOptions) -> Result<SlurmNodes> {
+    let output = run_scontrol_command(options, &NODES_ARGS)?;
+    let result: SlurmNodes = serde_json::from_str(&output)?;
+    debug!("Parsed {} nodes", result.nodes.len());
+    Ok(result)
+}
+
+fn query_jobs(options: &ScontrolOptions) -> Result<SlurmJobs> {
+    let output = run_scontrol_command(options, &JOBS_ARGS)?;
+    let result: SlurmJobs = serde_json::from_str(&output)?;
+    debug!("Parsed {} jobs", result.jobs.len());
+    Ok(result)
+}
+
+fn query_partitions(options: &ScontrolOptions) -> Result<SlurmPartitions> {
+    let output = run_scontrol_command(options, &PARTITIONS_ARGS)?;
+    let result: SlurmPartitions = serde_json::from_str(&output)?;
+    debug!("Parsed {} partitions", result.partitions.len());
+    Ok(result)
+}
+
+fn query_qos(options: &ScontrolOptions) -> Result<SlurmQos> {
+    let output = run_scontrol_command(options, &QOS_ARGS)?;
+    let result: SlurmQos = serde_json::from_str(&output)?;
+    debug!("Parsed {} QOS", result.qos.len());
+    Ok(result)
+}
+
+fn query_reservations(options: &ScontrolOptions) -> Result<SlurmReservations> {
+    let output = run_scontrol_command(options, &RESERVATIONS_ARGS)?;
+    let result: SlurmReservations = serde_json::from_str(&output)?;
+    debug!("Parsed {} reservations", result.reservations.len());
+    Ok(result)
+}
+
+/// Wait for an optional background query and propagate its error
+fn join_query<T>(handle: Option<std::thread::ScopedJoinHandle<Result<T>>>) -> Result<Option<T>> {
+    handle
+        .map(|handle| {
+            handle
+                .join()
+                .unwrap_or_else(|_| bail!("Query thread panicked"))
+        })
+        .transpose()
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum TableStyle {
+    Markdown,
+    Ascii,
+    Modern,
+    /// Like modern, with rounded corners
+    Rounded,
+    /// Like the output of PostgreSQL's psql, with only inner borders
+    Psql,
+    /// Space-separated columns without borders, suitable for grep
+    Plain,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    /// Color only when writing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+/// Colors of the gres_status glyphs, states and usage
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Palette {
+    /// Green for idle and red for used
+    Default,
+    /// Blue instead of green, which is hard to tell from red with red-green color blindness
+    Colorblind,
+}
+
+impl Palette {
+    /// The color standing in for `color` of the default palette
+    fn color(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::Colorblind, Color::Green) => Color::Blue,
+            (Self::Colorblind, Color::BrightGreen) => Color::BrightBlue,
+            // Blue now means idle, so set apart what was blue before
+            (Self::Colorblind, Color::Blue) => Color::BrightWhite,
+            (Self::Colorblind, Color::BrightBlue) => Color::White,
+            (_, color) => color,
+        }
+    }
+}
+
+/// Which amount the CPU and memory columns put over the total
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Ratio {
+    /// Idle CPUs and unallocated memory, e.g. "cpus_free 48/64"
+    Free,
+    /// Allocated CPUs and memory, e.g. "cpus_used 16/64"
+    Used,
+}
+
+impl Ratio {
+    /// Headers of the CPU and memory columns, which name the numerator
+    fn headers(self) -> [(&'static str, &'static str); 2] {
+        match self {
+            Self::Free => [
+                ("cpus_available", "cpus_free"),
+                ("memory_available", "memory_free"),
+            ],
+            Self::Used => [
+                ("cpus_available", "cpus_used"),
+                ("memory_available", "memory_used"),
+            ],
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+    /// One JSON object per node and line
+    Ndjson,
+}
+
+/// Machine-readable output of a single query
+#[derive(Serialize)]
+struct Report<'a> {
+    generated_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    controller_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cluster: Option<&'a str>,
+    /// Partition the nodes were selected by, all partitions if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    partition: Option<&'a str>,
+    /// Partitions and QOS whose jobs are counted as preemptible on every listed node
+    preemption: &'a Preemption,
+    nodes: ReportNodes<'a>,
+}
+
+/// Nodes of a report, either complete or projected onto `--fields`
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ReportNodes<'a> {
+    Full(&'a [NodeStatus]),
+    Projected(Vec<Map<String, Value>>),
+}
+
+/// Keys of a node in the JSON output, which `--fields` can select
+const NODE_FIELDS: [&str; 44] = [
+    "hostname",
+    "state",
+    "partitions",
+    "cpus",
+    "idle_cpus",
+    "memory",
+    "free_memory",
+    "gres",
+    "shards",
+    "shards_used",
+    "gres_raw",
+    "gres_used_raw",
+    "gres_total",
+    "gres_used",
+    "gres_suspended",
+    "gres_preempted",
+    "gres_idle",
+    "gres_drained",
+    "gres_offline",
+    "gres_powered_down",
+    "effective_free",
+    "idle_indices",
+    "models",
+    "features",
+    "cpu_load",
+    "boot_time",
+    "slurmd_start_time",
+    "slurmd_version",
+    "weight",
+    "tres",
+    "tres_used",
+    "boards",
+    "sockets",
+    "cores_per_socket",
+    "threads_per_core",
+    "address",
+    "node_hostname",
+    "next_free_at",
+    "running_jobs",
+    "users",
+    "reservations",
+    "reason",
+    "reason_set_by_user",
+    "idle_change",
+];
+
+/// Only the `fields` of a node. Optional fields that are unknown for the node
+/// are left out as in the full output.
+fn project_fields(node: &NodeStatus, fields: &[String]) -> Result<Map<String, Value>> {
+    let Value::Object(mut full) = serde_json::to_value(node)? else {
+        bail!("Node {} did not serialize to an object", node.hostname);
+    };
+    Ok(fields
+        .iter()
+        .filter_map(|field| full.remove_entry(field))
+        .collect())
+}
+
+/// The parts of a saved report needed to compare against it
+#[derive(Deserialize)]
+struct Snapshot {
+    generated_at: String,
+    nodes: Vec<SnapshotNode>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotNode {
+    hostname: String,
+    gres_idle: usize,
+}
+
+impl Snapshot {
+    fn load(path: &std::path::Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading snapshot {} failed", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Parsing snapshot {} failed", path.display()))
+    }
+
+    /// Set the change of idle GRES of each node that is also in the snapshot
+    fn compare(&self, statuses: &mut [NodeStatus]) {
+        let previous: HashMap<&str, usize> = self
+            .nodes
+            .iter()
+            .map(|node| (node.hostname.as_str(), node.gres_idle))
+            .collect();
+        for node in statuses {
+            node.idle_change = previous
+                .get(node.hostname.as_str())
+                .map(|&idle| node.gres_idle as i64 - idle as i64);
+        }
+    }
+}
+
+fn apply_style_to_table(style: Option<TableStyle>, table: &mut Table) -> &Table {
+    match style {
+        Some(TableStyle::Markdown) | None => table.with(Style::markdown()),
+        Some(TableStyle::Ascii) => table.with(Style::ascii()),
+        Some(TableStyle::Modern) => table.with(Style::modern()),
+        Some(TableStyle::Rounded) => table.with(Style::rounded()),
+        Some(TableStyle::Psql) => table.with(Style::psql()),
+        Some(TableStyle::Plain) => table.with(Style::blank()),
+    }
+}
+
+/// Optional columns that are only shown when requested
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+enum Column {
+    Features,
+    Load,
+    Jobs,
+    Users,
+    Partitions,
+    Reserved,
+    Reservation,
+    GpuUtil,
+    EffectiveFree,
+    NextFree,
+    IdleIdx,
+    Address,
+    Uptime,
+    SlurmdVersion,
+    TresUsed,
+    Weight,
+    Reason,
+    IdleChange,
+    /// Every state of the node, unabbreviated and uncolored
+    StateFull,
+    /// Boards, sockets, cores per socket and threads per core, e.g. "1:2:32:2"
+    Topology,
+    /// Configured trackable resources
+    Tres,
+    /// The gres string as reported by Slurm
+    GresRaw,
+    /// The gres_used string as reported by Slurm
+    GresUsedRaw,
+}
+
+impl Column {
+    fn name(&self) -> String {
+        self.to_possible_value()
+            .expect("no column is skipped")
+            .get_name()
+            .to_owned()
+    }
+}
+
+fn hide_unselected_columns(columns: &[Column], table: &mut Table) {
+    for column in Column::value_variants() {
+        if !columns.contains(column) {
+            table.with(Disable::column(ByColumnName::new(column.name())));
+        }
+    }
+}
+
+/// Columns of numbers or ratios, which are easier to compare right-aligned
+const NUMERIC_COLUMNS: [&str; 9] = [
+    "cpus_available",
+    "memory_available",
+    "load",
+    "jobs",
+    "gpu_util",
+    "effective_free",
+    "weight",
+    "idle_change",
+    "next_free",
+];
+
+fn align_numeric_columns(table: &mut Table) {
+    for column in NUMERIC_COLUMNS {
+        table.modify(ByColumnName::new(column), Alignment::right());
+    }
+}
+
+/// Width of the terminal that stdout is connected to, as exported in `COLUMNS`,
+/// if it is one
+fn terminal_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
+
+/// Widest content of a column, ignoring color codes
+fn column_width(tabled_nodes: &[TableNode], header: &str) -> usize {
+    let Some(index) = TableNode::headers().iter().position(|name| name == header) else {
+        return 0;
+    };
+    tabled_nodes
+        .iter()
+        .map(|node| get_string_width(&node.fields()[index]))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Name the numerator of the CPU and memory columns in their headers
+fn rename_ratio_headers(table: &mut Table, ratio: Ratio) {
+    for (field, header) in ratio.headers() {
+        table.modify(
+            Locator::content(field),
+            Format::content(|_| header.to_owned()),
+        );
+    }
+}
+
+/// Wrap the gres_status column onto several lines so that the table fits in `width`
+fn fit_gres_status(table: &mut Table, tabled_nodes: &[TableNode], width: usize) {
+    let excess = table.total_width().saturating_sub(width);
+    if excess == 0 {
+        return;
+    }
+    let header = "gres_status";
+    let content_width = column_width(tabled_nodes, header);
+    let target = content_width.saturating_sub(excess).max(header.len());
+    if target < content_width {
+        table.modify(ByColumnName::new(header), Width::wrap(target));
+    }
+}
+
+/// Columns of long names or lists that are cut short on narrow terminals
+const TRUNCATED_COLUMNS: [&str; 4] = ["hostname", "partitions", "features", "reason"];
+
+/// Narrowest width that a column is truncated to
+const MIN_TRUNCATED_WIDTH: usize = 12;
+
+/// Truncate `TRUNCATED_COLUMNS` with an ellipsis until the table fits in `width`
+fn truncate_long_columns(table: &mut Table, tabled_nodes: &[TableNode], width: usize) {
+    for header in TRUNCATED_COLUMNS {
+        let excess = table.total_width().saturating_sub(width);
+        if excess == 0 {
+            break;
+        }
+        let content_width = column_width(tabled_nodes, header);
+        let target = content_width
+            .saturating_sub(excess)
+            .max(MIN_TRUNCATED_WIDTH)
+            .max(header.len());
+        if target < content_width {
+            table.modify(
+                ByColumnName::new(header),
+                Width::truncate(target).suffix("…"),
+            );
+        }
+    }
+}
+
+/// Default columns that may be hidden on narrow terminals, least important first
+const DROP_PRIORITY: [&str; 2] = ["state", "memory_available"];
+
+/// Width of the padding on both sides of a cell and of the separator after it
+const COLUMN_PADDING: usize = 3;
+
+/// The columns to hide, in the order of `widths`, so that a table of `table_width`
+/// fits in `limit`, given how much narrower hiding each column makes the table
+fn columns_to_drop<'a>(
+    table_width: usize,
+    widths: &[(&'a str, usize)],
+    limit: usize,
+) -> Vec<&'a str> {
+    let mut remaining = table_width;
+    let mut dropped = Vec::new();
+    for &(column, width) in widths {
+        if remaining <= limit {
+            break;
+        }
+        dropped.push(column);
+        remaining = remaining.saturating_sub(width);
+    }
+    dropped
+}
+
+/// Hide default columns by `DROP_PRIORITY` until the table fits in `width`
+fn drop_low_priority_columns(table: &mut Table, tabled_nodes: &[TableNode], width: usize) {
+    let widths: Vec<(&str, usize)> = DROP_PRIORITY
+        .iter()
+        .map(|&column| {
+            let content_width = column_width(tabled_nodes, column).max(column.len());
+            (column, content_width + COLUMN_PADDING)
+        })
+        .collect();
+    for column in columns_to_drop(table.total_width(), &widths, width) {
+        table.with(Disable::column(ByColumnName::new(column)));
+    }
+}
+
+/// Keys to sort the listed nodes by
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+enum SortKey {
+    Hostname,
+    /// Most idle GRES first
+    Idle,
+    /// Most idle plus preemptible GRES first
+    EffectiveFree,
+    /// Nodes whose GRES become free soonest first, unlimited jobs last
+    NextFree,
+    /// Most recently booted nodes first
+    Uptime,
+    /// Lowest scheduling weight first, as Slurm prefers them
+    Weight,
+    /// Most idle CPUs first
+    IdleCpus,
+    /// Nodes by their most important state, down and drained nodes first
+    State,
+}
+
+fn sort_statuses(statuses: &mut [NodeStatus], key: SortKey) {
+    match key {
+        SortKey::Hostname => statuses.sort_by(|a, b| a.hostname.cmp(&b.hostname)),
+        SortKey::Idle => statuses.sort_by_key(|node| std::cmp::Reverse(node.gres_idle)),
+        SortKey::EffectiveFree => {
+            statuses.sort_by_key(|node| std::cmp::Reverse(node.effective_free))
+        }
+        SortKey::Weight => statuses.sort_by_key(|node| node.weight),
+        SortKey::IdleCpus => statuses.sort_by_key(|node| std::cmp::Reverse(node.idle_cpus)),
+        SortKey::State => {
+            statuses.sort_by_key(|node| dominant_state(&node.state).map_or(usize::MAX, state_rank))
+        }
+        SortKey::Uptime => statuses.sort_by_key(|node| std::cmp::Reverse(node.boot_time)),
+        SortKey::NextFree => statuses
+            .sort_by_key(|node| (node.gres_idle == 0, node.next_free_at.unwrap_or(u64::MAX))),
+    }
+}
+
+/// Numeric components of a version for comparing it, e.g. `[23, 11, 10]` for `23.11.10`
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+/// The most common slurmd version, preferring the newest on ties
+fn majority_version(statuses: &[NodeStatus]) -> Option<&str> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for node in statuses {
+        *counts.entry(node.slurmd_version.as_str()).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|&(a, a_count), &(b, b_count)| {
+            a_count
+                .cmp(&b_count)
+                .then_with(|| version_key(a).cmp(&version_key(b)))
+        })
+        .map(|(version, _)| version)
+}
+
+/// Keep only the nodes whose slurmd version differs from the most common one
+fn retain_version_mismatches(statuses: &mut Vec<NodeStatus>) {
+    if let Some(majority) = majority_version(statuses).map(str::to_owned) {
+        retain_statuses(statuses, "majority slurmd version", |node| {
+            node.slurmd_version != majority
+        });
+    }
+}
+
+/// Group nodes by GRES model, most idle first within each model
+fn sort_statuses_by_model(statuses: &mut [NodeStatus]) {
+    statuses.sort_by(|a, b| {
+        a.gres
+            .cmp(&b.gres)
+            .then_with(|| b.gres_idle.cmp(&a.gres_idle))
+    });
+}
+
+/// Environment variable enabling `--accessible`
+const ACCESSIBLE_ENV: &str = "LSGRES_ACCESSIBLE";
+
+/// Positional argument that lists nodes by CPUs instead of by a GRES
+const CPU_MODE: &str = "cpu";
+
+const GPU_TYPE: &str = "gpu";
+const SHARD_TYPE: &str = "shard";
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the first node with enough idle GRES of a model, exiting with 1 if there is none
+    Query {
+        /// Model of the GRES, e.g. "a100", or "a100,h100" for either
+        #[arg(short, long)]
+        model: String,
+
+        /// Type of GRES to count, e.g. "shard" for GPU sharding or "fpga"
+        #[arg(long = "type", default_value = GPU_TYPE, value_parser = parse_gres_type)]
+        gres_type: String,
+
+        /// JSON file mapping GRES names to regular expressions, e.g. {"a100": "a100-sxm4-.*"}
+        #[arg(long)]
+        alias_file: Option<std::path::PathBuf>,
+
+        /// Minimum number of idle GRES on the node
+        #[arg(short, long, default_value_t = 1)]
+        count: usize,
+
+        /// Only consider nodes in this partition
+        #[arg(short, long)]
+        partition: Option<String>,
+    },
+    /// Show the GPUs held by your running jobs in any partition
+    Me {
+        /// User whose jobs to show, by default `$USER`
+        #[arg(short, long)]
+        user: Option<String>,
+
+        /// Style of the printed table, by default "markdown"
+        #[arg(short, long, value_enum)]
+        style: Option<TableStyle>,
+    },
+}
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "List generic resource (GRES) in a Slurm cluster by node",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Names of the GRES, e.g. "gpu", "h100" or "a100,h100" for either,
+    /// or "cpu" to list every node by idle CPUs
+    #[arg(required_unless_present_any = ["interactive", "job"])]
+    gres: Option<String>,
+
+    /// Type of GRES to count and match the names against, e.g. "shard" for GPU sharding
+    /// or "fpga"
+    #[arg(long = "type", default_value = GPU_TYPE, value_parser = parse_gres_type)]
+    gres_type: String,
+
+    /// Only show the nodes allocated to this job, of any GRES unless names are given
+    #[arg(long, value_name = "ID")]
+    job: Option<u64>,
+
+    /// Pick the GPU model and partition from those in the cluster if they are not given
+    #[arg(long, conflicts_with = "dry_run")]
+    interactive: bool,
+
+    /// Selet which partition to show, e.g. "gpu", "interactive",
+    /// by default the one in the config file, if any
+    #[arg(short, long)]
+    partition: Option<String>,
+
+    /// Show nodes of all partitions, ignoring the default partition in the config file
+    #[arg(long, conflicts_with = "partition")]
+    all_partitions: bool,
+
+    /// JSON file mapping GRES names to regular expressions, e.g. {"a100": "a100-sxm4-.*"}
+    #[arg(long)]
+    alias_file: Option<std::path::PathBuf>,
+
+    /// Only show nodes in any of these states, e.g. "IDLE,MIXED"
+    #[arg(long, value_delimiter = ',', value_parser = parse_state)]
+    state: Vec<String>,
+
+    /// Only show nodes with all of these active features, e.g. "nvlink,infiniband"
+    #[arg(long, value_delimiter = ',')]
+    feature: Vec<String>,
+
+    /// Style of the printed table, by default "markdown"
+    #[arg(short, long, value_enum)]
+    style: Option<TableStyle>,
+
+    /// Optional columns to show in addition to the default ones
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    columns: Vec<Column>,
+
+    /// When to color the output
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Colors to use, by default the one in the config file or "default"
+    #[arg(long, value_enum)]
+    palette: Option<Palette>,
+
+    /// Output format, either a table or JSON for scripts
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
+    /// Whether the CPU and memory columns show the free or the used amount over the total
+    #[arg(long, value_enum, default_value_t = Ratio::Free)]
+    ratio: Ratio,
+
+    /// Show the used CPUs and memory over the total, same as `--ratio used`
+    #[arg(long, conflicts_with = "ratio")]
+    show_used: bool,
+
+    /// Show the memory column as a percentage of the total, e.g. "23% free"
+    #[arg(long)]
+    mem_percent: bool,
+
+    /// Number of decimals of the memory column in GB
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=6))]
+    mem_precision: u8,
+
+    /// Only include these keys of each node in the JSON output, e.g. "hostname,gres_idle"
+    #[arg(long, value_delimiter = ',', value_parser = PossibleValuesParser::new(NODE_FIELDS))]
+    fields: Vec<String>,
+
+    /// Also write the JSON output to this file, for a later `--diff`
+    #[arg(long)]
+    save: Option<std::path::PathBuf>,
+
+    /// Show the change of idle GRES per node since a snapshot written by `--save`
+    #[arg(long)]
+    diff: Option<std::path::PathBuf>,
+
+    /// Only print a one-line summary of idle GRES per model
+    #[arg(long)]
+    total_only: bool,
+
+    /// Only print the number of nodes that match the filters
+    #[arg(long, conflicts_with = "total_only")]
+    count: bool,
+
+    /// Do not print the query time and cluster name under the table
+    #[arg(long)]
+    no_caption: bool,
+
+    /// Print the query time above the table
+    #[arg(long)]
+    timestamp: bool,
+
+    /// Only show nodes with at least this many idle CPUs
+    #[arg(long)]
+    cpus_free: Option<usize>,
+
+    /// Only show nodes with at least this many idle GRES
+    #[arg(long, value_name = "N")]
+    free: Option<usize>,
+
+    /// Only show nodes with at least this many GB of unallocated memory
+    #[arg(long)]
+    min_mem: Option<usize>,
+
+    /// Hide nodes covered by an active reservation
+    #[arg(long)]
+    hide_reserved: bool,
+
+    /// Show nodes that are powered down by power saving or booting, the default
+    #[arg(long, overrides_with = "exclude_powered_down")]
+    include_powered_down: bool,
+
+    /// Hide nodes that are powered down by power saving or booting
+    #[arg(long)]
+    exclude_powered_down: bool,
+
+    /// Count GRES of powered down nodes as idle in the `--total-only` summary
+    /// and the `--free` filter, as they become available once the node boots
+    #[arg(long)]
+    count_powered_down: bool,
+
+    /// Warn about nodes whose GRES counts disagree with the configured and used counts
+    /// in their TRES
+    #[arg(long)]
+    verify_tres: bool,
+
+    /// Only show nodes whose slurmd version differs from the most common one
+    #[arg(long)]
+    version_mismatch_only: bool,
+
+    /// Sort the nodes instead of keeping the order reported by Slurm
+    #[arg(long, value_enum)]
+    sort: Option<SortKey>,
+
+    /// Group the nodes by GRES model, with the most idle first within each model
+    #[arg(long, conflicts_with = "sort")]
+    sort_by_model: bool,
+
+    /// Abbreviate states like sinfo, e.g. "MIX+DRN"
+    #[arg(long)]
+    short_state: bool,
+
+    /// Prefix states and GRES with icons from a Nerd Font, or emoji with `--icons=emoji`
+    #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "nerd")]
+    icons: Option<IconSet>,
+
+    /// Show every column except those comparing against a snapshot
+    #[arg(short, long)]
+    long: bool,
+
+    /// Show why nodes that are not idle were drained or set down, same as `-c reason`
+    #[arg(long)]
+    show_reason: bool,
+
+    /// Print a legend of the gres_status characters under the table
+    #[arg(long, overrides_with = "no_legend")]
+    legend: bool,
+
+    /// Do not print the legend, even if enabled in the config file
+    #[arg(long)]
+    no_legend: bool,
+
+    /// Wrap the gres_status column and hide less important columns to fit the terminal,
+    /// the default
+    #[arg(long, overrides_with = "no_fit_width")]
+    fit_width: bool,
+
+    /// Never shrink the table, even if it is wider than the terminal
+    #[arg(long, conflicts_with = "max_width")]
+    no_fit_width: bool,
+
+    /// Width to fit the table in, by default $COLUMNS when writing to a terminal
+    #[arg(long, value_name = "COLS")]
+    max_width: Option<usize>,
+
+    /// Remove this prefix from the displayed hostnames, e.g. "prod-cluster-"
+    #[arg(long, value_name = "STR")]
+    strip_prefix: Option<String>,
+
+    /// Remove the longest prefix ending in a separator that all displayed hostnames share
+    #[arg(long, conflicts_with = "strip_prefix")]
+    trim_common: bool,
+
+    /// Maximum width of list-valued columns before they are truncated
+    #[arg(long, default_value_t = 30)]
+    list_width: usize,
+
+    /// Character for GRES in use in the gres_status column [default: u]
+    #[arg(long, value_parser = parse_glyph)]
+    glyph_used: Option<char>,
+
+    /// Character for GRES held by suspended jobs in the gres_status column [default: s]
+    #[arg(long, value_parser = parse_glyph)]
+    glyph_suspended: Option<char>,
+
+    /// Character for GRES held by preemptible jobs in the gres_status column [default: p]
+    #[arg(long, value_parser = parse_glyph)]
+    glyph_preempted: Option<char>,
+
+    /// Character for idle GRES in the gres_status column [default: i]
+    #[arg(long, value_parser = parse_glyph)]
+    glyph_idle: Option<char>,
+
+    /// Character for individually drained GRES in the gres_status column [default: d]
+    #[arg(long, value_parser = parse_glyph)]
+    glyph_drained: Option<char>,
+
+    /// Character for unallocated GRES on down or drained nodes in the gres_status column
+    /// [default: o]
+    #[arg(long, value_parser = parse_glyph)]
+    glyph_offline: Option<char>,
+
+    /// Character for unallocated GRES on powered down or booting nodes in the gres_status
+    /// column [default: z]
+    #[arg(long, value_parser = parse_glyph)]
+    glyph_powered_down: Option<char>,
+
+    /// Color of GRES in use in the gres_status column [default: red]
+    #[arg(long, value_parser = parse_color)]
+    color_used: Option<String>,
+
+    /// Color of GRES held by suspended jobs in the gres_status column [default: cyan]
+    #[arg(long, value_parser = parse_color)]
+    color_suspended: Option<String>,
+
+    /// Color of GRES held by preemptible jobs in the gres_status column [default: yellow]
+    #[arg(long, value_parser = parse_color)]
+    color_preempted: Option<String>,
+
+    /// Color of idle GRES in the gres_status column [default: green]
+    #[arg(long, value_parser = parse_color)]
+    color_idle: Option<String>,
+
+    /// Color of individually drained GRES in the gres_status column [default: magenta]
+    #[arg(long, value_parser = parse_color)]
+    color_drained: Option<String>,
+
+    /// Color of unallocated GRES on down or drained nodes [default: bright black]
+    #[arg(long, value_parser = parse_color)]
+    color_offline: Option<String>,
+
+    /// Color of unallocated GRES on powered down or booting nodes [default: blue]
+    #[arg(long, value_parser = parse_color)]
+    color_powered_down: Option<String>,
+
+    /// Number of GRES each glyph stands for, by default scaled to fit dense nodes
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    glyph_scale: Option<u16>,
+
+    /// Spell out GRES counts, without color or box-drawing characters, for screen readers
+    #[arg(long)]
+    accessible: bool,
+
+    /// Show gres_status as a fixed-width bar instead of one glyph per GRES
+    #[arg(long)]
+    bar: bool,
+
+    /// Number of cells in the bar
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..))]
+    bar_width: u16,
+
+    /// Draw the bar with ASCII characters, the default without a UTF-8 locale
+    #[arg(long)]
+    ascii_glyphs: bool,
+
+    /// Partition whose running jobs can be preempted, repeatable for several, by default
+    /// from $LSGRES_PREEMPT_PARTITION, the config file, the partitions' preempt modes
+    /// and the QOS preempt lists, or "preempted"
+    #[arg(long, value_name = "NAME")]
+    preempt_partition: Vec<String>,
+
+    /// QOS whose running jobs can be preempted in any partition, repeatable for several
+    #[arg(long, value_name = "NAME")]
+    preempt_qos: Vec<String>,
+
+    /// Partition you submit to, coloring GRES held by preemptible jobs green if its jobs
+    /// can preempt them by a higher priority tier
+    #[arg(long, value_name = "NAME")]
+    my_partition: Option<String>,
+
+    /// Count GRES held by preemptible jobs as free in the gpu_util column
+    #[arg(long)]
+    exclude_preempted: bool,
+
+    /// Query a specific cluster of a federated Slurm setup
+    #[arg(short = 'M', long, value_parser = parse_cluster_name)]
+    cluster: Option<String>,
+
+    /// Retry scontrol this many times with exponential backoff on transient failures
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u32).range(0..=10))]
+    retries: u32,
+
+    /// Log query timings, parsing fallbacks and why nodes were filtered out to stderr
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Read nodes.json, jobs.json, reservations.json and partitions.json saved from
+    /// `scontrol show ... --json`, and qos.json from `sacctmgr show qos --json`, in this
+    /// directory instead of querying Slurm, e.g. to reproduce or profile a listing
+    #[arg(long, value_name = "DIR")]
+    input_dir: Option<std::path::PathBuf>,
+
+    /// Query again every this many seconds, redrawing the table only when it changes
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["save", "diff", "total_only", "count", "dry_run"]
+    )]
+    refresh_interval: Option<u64>,
+
+    /// Print the scontrol commands to stderr before running them
+    #[arg(long)]
+    print_commands: bool,
+
+    /// Print the scontrol commands to stderr and exit without running them, noting those
+    /// that only run depending on the result of another
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl Cli {
+    /// Why the node does not match the filters, if it does not
+    fn exclusion_reason(&self, gres_filters: &[GresFilter], node: &Node) -> Option<String> {
+        if !self.cpu_mode() && !gres_filters.is_empty() {
+            // Only match GRES of the counted type, e.g. not a GPU for an FPGA
+            let mut entries: Vec<&str> = split_gres_entries(&node.gres)
+                .into_iter()
+                .filter(|entry| gres_type(entry.trim()) == self.gres_type)
+                .collect();
+            // GRES only tracked in TRES have no model to match
+            if entries.is_empty() && tres_gres_count(&node.tres, &self.gres_type).is_some() {
+                entries.push(&self.gres_type);
+            }
+            let matches = entries
+                .into_iter()
+                .any(|entry| gres_filters.iter().any(|filter| filter.matches(entry)));
+            if !matches {
+                return Some(format!(
+                    "no {} GRES in \"{}\" matches",
+                    self.gres_type, node.gres
+                ));
+            }
+        }
+        if let Some(ref partition) = self.partition {
+            if !node.partitions.contains(partition) {
+                return Some(format!("not in partition {}", partition));
+            }
+        }
+        if !self.state.is_empty() && !node.state.iter().any(|s| self.state.contains(s)) {
+            return Some(format!("state {} does not match", node.state.join(",")));
+        }
+        if !self.feature.is_empty() {
+            let features = normalize_features(&node.active_features);
+            if let Some(missing) = self.feature.iter().find(|f| !features.contains(f)) {
+                return Some(format!("missing feature {}", missing));
+            }
+        }
+        None
+    }
+
+    /// The palette from the command line, the config file or the default, in that order
+    fn palette(&self, config: &Config) -> Result<Palette> {
+        match (self.palette, config.palette.as_deref()) {
+            (Some(palette), _) => Ok(palette),
+            (None, Some(name)) => Palette::from_str(name, false)
+                .ok()
+                .with_context(|| format!("Unknown palette \"{}\" in the config file", name)),
+            (None, None) => Ok(Palette::Default),
+        }
+    }
+
+    /// Whether to render for screen readers, also enabled by the config file
+    /// or a non-empty `LSGRES_ACCESSIBLE`
+    fn accessible(&self, config: &Config) -> bool {
+        self.accessible
+            || config.accessible.unwrap_or(false)
+            || std::env::var_os(ACCESSIBLE_ENV).is_some_and(|value| !value.is_empty())
+    }
+
+    /// Amount the CPU and memory columns put over the total
+    fn ratio(&self) -> Ratio {
+        if self.show_used {
+            Ratio::Used
+        } else {
+            self.ratio
+        }
+    }
+
+    /// Whether all nodes are listed by CPU availability instead of by GRES
+    fn cpu_mode(&self) -> bool {
+        self.gres.as_deref() == Some(CPU_MODE)
+    }
+
+    /// Optional columns to show, including those implied by other flags
+    fn columns(&self) -> Vec<Column> {
+        let mut columns = if self.long {
+            Column::value_variants()
+                .iter()
+                .copied()
+                .filter(|&column| column != Column::IdleChange)
+                .collect()
+        } else {
+            self.columns.clone()
+        };
+        if !self.feature.is_empty() && !columns.contains(&Column::Features) {
+            columns.push(Column::Features);
+        }
+        if self.show_reason && !columns.contains(&Column::Reason) {
+            columns.push(Column::Reason);
+        }
+        if self.diff.is_some() && !columns.contains(&Column::IdleChange) {
+            columns.push(Column::IdleChange);
+        }
+        columns
+    }
+
+    /// Preempt partitions and QOS given on the command line, in the environment or in the
+    /// config file, which override those detected from the partitions
+    fn configured_preemption(&self, config: &Config) -> Option<Preemption> {
+        let qos = if self.preempt_qos.is_empty() {
+            config.preempt_qos.clone()
+        } else {
+            Some(self.preempt_qos.clone())
+        };
+        match (self.configured_preempt_partitions(config), qos) {
+            (None, None) => None,
+            (partitions, qos) => Some(Preemption {
+                partitions: partitions.unwrap_or_default(),
+                qos: qos.unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// Preempt partitions given on the command line, in the environment or in the config
+    /// file, in that order
+    fn configured_preempt_partitions(&self, config: &Config) -> Option<Vec<String>> {
+        if !self.preempt_partition.is_empty() {
+            return Some(self.preempt_partition.clone());
+        }
+        if let Some(value) = std::env::var(PREEMPT_PARTITION_ENV)
+            .ok()
+            .filter(|value| !value.is_empty())
+        {
+            return Some(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            );
+        }
+        config.preempt_partitions.clone()
+    }
+
+    /// Whether the partitions are needed, to detect the preemptible ones or for
+    /// `--my-partition`
+    fn needs_partitions(&self, config: &Config) -> bool {
+        self.my_partition.is_some() || self.configured_preemption(config).is_none()
+    }
+
+    /// Whether the QOS are needed to detect the preemptible ones
+    fn needs_qos(&self, config: &Config) -> bool {
+        self.configured_preemption(config).is_none()
+    }
+
+    /// Whether jobs are needed before knowing which partitions are preemptible
+    fn needs_jobs(&self, config: &Config) -> bool {
+        self.configured_preemption(config).is_some()
+            || self.job.is_some()
+            || self
+                .columns()
+                .iter()
+                .any(|column| matches!(column, Column::Jobs | Column::Users | Column::NextFree))
+    }
+
+    fn needs_reservations(&self) -> bool {
+        self.hide_reserved
+            || self
+                .columns()
+                .iter()
+                .any(|column| matches!(column, Column::Reserved | Column::Reservation))
+    }
+
+    /// Arguments of every scontrol query for the requested output, with the condition
+    /// of those that depend on the result of another
+    fn planned_queries(&self, config: &Config) -> Vec<(&'static [&'static str], Option<&str>)> {
+        let mut queries: Vec<(&[&str], _)> = Vec::new();
+        if self.needs_partitions(config) {
+            queries.push((&PARTITIONS_ARGS, None));
+        }
+        if self.needs_qos(config) {
+            queries.push((&QOS_ARGS, None));
+        }
+        queries.push((&NODES_ARGS, None));
+        if self.needs_jobs(config) {
+            queries.push((&JOBS_ARGS, None));
+        } else {
+            queries.push((&JOBS_ARGS, Some("if a preemptible partition or QOS exists")));
+        }
+        if self.needs_reservations() {
+            queries.push((&RESERVATIONS_ARGS, None));
+        }
+        queries
+    }
+}
+
+/// Print the first node with at least `count` idle GRES of `gres_type` of the models
+/// matching the filters, returning whether one was found
+fn run_query(
+    scontrol_options: &ScontrolOptions,
+    gres_filters: &[GresFilter],
+    gres_type: &str,
+    count: usize,
+    partition: Option<&str>,
+) -> Result<bool> {
+    let slurm_nodes = query_nodes(scontrol_options)?;
+    for node in slurm_nodes
+        .nodes
+        .iter()
+        .filter(|node| partition.is_none_or(|p| node.partitions.iter().any(|n| n == p)))
+    {
+        let status = NodeStatus::from_node(node, gres_type, None, None)?;
+        match status.idle_matching(gres_filters) {
+            Some(idle) if idle >= count => {
+                println!("{} {}", status.hostname, idle);
+                return Ok(true);
+            }
+            _ => {}
+        }
+    }
+    Ok(false)
+}
+
+/// GPUs of a model allocated to a job on one node
+#[derive(Tabled)]
+struct TableAllocation {
+    job_id: u64,
+    partition: String,
+    hostname: String,
+    gres: String,
+    count: usize,
+    preemptible: &'static str,
+}
+
+/// Print the GPUs allocated to the running jobs of `user` on each node
+fn run_me(
+    scontrol_options: &ScontrolOptions,
+    user: &str,
+    preemption: &Preemption,
+    style: Option<TableStyle>,
+) -> Result<()> {
+    let slurm_jobs = query_jobs(scontrol_options)?;
+    let mut allocations = Vec::new();
+    for job in slurm_jobs
+        .jobs
+        .iter()
+        .filter(|job| job.is_running() && job.user_name == user)
+    {
+        for (hostname, model, count) in job.allocations(GPU_TYPE)? {
+            allocations.push(TableAllocation {
+                job_id: job.job_id,
+                partition: job.partition.clone(),
+                hostname,
+                gres: model,
+                count,
+                preemptible: if preemption.is_preemptible(job) {
+                    "yes"
+                } else {
+                    "no"
+                },
+            });
+        }
+    }
+    if allocations.is_empty() {
+        println!("No GPUs allocated to running jobs of {}", user);
+        return Ok(());
+    }
+    let total: usize = allocations.iter().map(|a| a.count).sum();
+    let preemptible: usize = allocations
+        .iter()
+        .filter(|a| a.preemptible == "yes")
+        .map(|a| a.count)
+        .sum();
+    let nodes: BTreeSet<&str> = allocations.iter().map(|a| a.hostname.as_str()).collect();
+    let mut table = Table::new(&allocations);
+    apply_style_to_table(style, &mut table);
+    println!("{}", table);
+    println!(
+        "{} GPUs on {} nodes, {} of them in preemptible jobs from {}",
+        total,
+        nodes.len(),
+        preemptible,
+        preemption.describe()
+    );
+    Ok(())
+}
+
+/// Hostnames of the nodes allocated to a job
+fn job_hosts(slurm_jobs: Option<&SlurmJobs>, job_id: u64) -> Result<BTreeSet<String>> {
+    let job = slurm_jobs
+        .and_then(|slurm_jobs| slurm_jobs.jobs.iter().find(|job| job.job_id == job_id))
+        .with_context(|| format!("Job {} not found", job_id))?;
+    if job.nodes.is_empty() {
+        bail!(
+            "Job {} has no nodes allocated, it is {}",
+            job_id,
+            job.job_state.join(",")
+        );
+    }
+    Ok(hostlist::expand(&job.nodes)?.into_iter().collect())
+}
+
+/// Let the user pick the GPU model and the partition if they were not given
+fn pick_filters(cli: &mut Cli, scontrol_options: &ScontrolOptions) -> Result<()> {
+    let slurm_nodes = query_nodes(scontrol_options)?;
+    if cli.gres.is_none() {
+        let mut models = BTreeSet::new();
+        for node in &slurm_nodes.nodes {
+            for status in GresStatus::parse_list(&node.gres, GPU_TYPE) {
+                models.insert(model_name(&status.model).to_owned());
+            }
+        }
+        let models: Vec<String> = models.into_iter().collect();
+        match prompt::pick("GPU model:", &models, false)? {
+            Some(model) => cli.gres = Some(model.to_owned()),
+            None => bail!("No GPUs found in the cluster"),
+        }
+    }
+    if cli.partition.is_none() && !cli.all_partitions {
+        let partitions: Vec<String> = slurm_nodes
+            .nodes
+            .iter()
+            .flat_map(|node| node.partitions.iter().cloned())
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+        cli.partition = prompt::pick("Partition:", &partitions, true)?.map(str::to_owned);
+    }
+    Ok(())
+}
+
+/// Longest prefix of all hostnames that ends with a separator such as `-`, so that
+/// e.g. `node10` and `node11` keep their numbers whole
+fn common_hostname_prefix(statuses: &[NodeStatus]) -> Option<String> {
+    let (first, rest) = statuses.split_first()?;
+    if rest.is_empty() {
+        return None;
+    }
+    let first = first.hostname.as_str();
+    let mut len = first.len();
+    for node in rest {
+        len = first
+            .bytes()
+            .zip(node.hostname.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count();
+    }
+    while !first.is_char_boundary(len) {
+        len -= 1;
+    }
+    let end = first[..len].rfind(|c: char| !c.is_ascii_alphanumeric())? + 1;
+    Some(first[..end].to_owned())
+}
+
+/// Keep the nodes for which `keep` holds, logging the others with `reason`
+fn retain_statuses(
+    statuses: &mut Vec<NodeStatus>,
+    reason: &str,
+    keep: impl Fn(&NodeStatus) -> bool,
+) {
+    statuses.retain(|node| {
+        let kept = keep(node);
+        if !kept {
+            debug!("Skipping {}: {}", node.hostname, reason);
+        }
+        kept
+    });
+}
+
+/// The parts of a node's status that `--refresh-interval` redraws the table for,
+/// leaving out those that change on every poll such as the CPU load
+#[derive(PartialEq, Debug)]
+struct WatchedStatus {
+    hostname: String,
+    state: Vec<String>,
+    idle_cpus: usize,
+    free_memory: usize,
+    gres_counts: [usize; CATEGORIES],
+    models: Vec<(String, [usize; CATEGORIES])>,
+}
+
+impl WatchedStatus {
+    fn from_status(node: &NodeStatus) -> Self {
+        Self {
+            hostname: node.hostname.clone(),
+            state: node.state.clone(),
+            idle_cpus: node.idle_cpus,
+            free_memory: node.free_memory,
+            gres_counts: node.gres_counts(),
+            models: node
+                .models
+                .iter()
+                .map(|model| (model.model.clone(), model.counts()))
+                .collect(),
+        }
+    }
+}
+
+/// Statuses kept between refreshes of `--refresh-interval`
+#[derive(Default)]
+struct Watch {
+    /// Statuses of the table on screen, which is only redrawn when they change
+    drawn: Option<Vec<WatchedStatus>>,
+}
+
+/// Query Slurm and print the matching nodes, skipping unchanged statuses when watching
+fn list_nodes(
+    cli: &Cli,
+    config: &Config,
+    scontrol_options: &ScontrolOptions,
+    gres_filters: &[GresFilter],
+    watch: Option<&mut Watch>,
+) -> Result<()> {
+    let accessible = cli.accessible(config);
+    let configured_preemption = cli.configured_preemption(config);
+    let needs_partitions = cli.needs_partitions(config);
+    let needs_qos = cli.needs_qos(config);
+    // Each query can take seconds on a busy controller, so run them concurrently.
+    // The partitions and QOS tell whether any job can be preemptible, so they are
+    // queried first, while the nodes are loading, to skip the often slowest query of
+    // all jobs.
+    let (slurm_nodes, slurm_jobs, slurm_reservations, slurm_partitions, preemption) =
+        std::thread::scope(|scope| {
+            let nodes_handle = scope.spawn(|| query_nodes(scontrol_options));
+            let reservations_handle = cli
+                .needs_reservations()
+                .then(|| scope.spawn(|| query_reservations(scontrol_options)));
+            let qos_handle = needs_qos.then(|| scope.spawn(|| query_qos(scontrol_options)));
+            let slurm_partitions = needs_partitions
+                .then(|| query_partitions(scontrol_options))
+                .transpose();
+            // Without accounting, or before sacctmgr supports JSON, go by the partitions
+            let slurm_qos = join_query(qos_handle).unwrap_or_else(|err| {
+                debug!("Cannot detect preemptible QOS: {:#}", err);
+                None
+            });
+            let known_partitions = slurm_partitions.as_ref().ok().and_then(Option::as_ref);
+            let preemption = configured_preemption
+                .unwrap_or_else(|| Preemption::detect(known_partitions, slurm_qos.as_ref()));
+            let needs_jobs = cli.needs_jobs(config) || preemption.applies(known_partitions);
+            let slurm_jobs = if needs_jobs {
+                query_jobs(scontrol_options).map(Some)
+            } else {
+                debug!("Skipping the job query without preemptible partitions or QOS");
+                Ok(None)
+            };
+            (
+                nodes_handle
+                    .join()
+                    .unwrap_or_else(|_| bail!("Query thread panicked")),
+                slurm_jobs,
+                join_query(reservations_handle),
+                slurm_partitions,
+                preemption,
+            )
+        });
+    let slurm_nodes = slurm_nodes?;
+    // Partitions are only essential to `--my-partition`, otherwise fall back to the default
+    let slurm_partitions = match slurm_partitions {
+        Err(err) if cli.my_partition.is_none() => {
+            debug!("Cannot detect preemptible partitions: {:#}", err);
+            None
+        }
+        result => result?,
+    };
+    // Whether GRES held by preemptible jobs could be preempted by jobs in `--my-partition`
+    let preemptible_by_me = match (slurm_partitions, cli.my_partition.as_deref()) {
+        (Some(slurm_partitions), Some(partition)) => {
+            Some(slurm_partitions.can_preempt(partition, &preemption.partitions)?)
+        }
+        _ => None,
+    };
+    let slurm_jobs = slurm_jobs?;
+    let job_hosts = match cli.job {
+        Some(job_id) => Some(job_hosts(slurm_jobs.as_ref(), job_id)?),
+        None => None,
+    };
+    let job_summary = slurm_jobs
+        .map(|slurm_jobs| JobSummary::from_jobs(&slurm_jobs.jobs, &cli.gres_type, &preemption))
+        .transpose()?;
+    let now = time::now();
+    let reservation_summary = slurm_reservations?
+        .map(|slurm_reservations| {
+            ReservationSummary::from_reservations(&slurm_reservations.reservations, now)
+        })
+        .transpose()?;
+    let generated_at = time::format_timestamp(now);
+    let controller_time = slurm_nodes
+        .last_update
+        .and_then(SlurmNumber::value)
+        .map(time::format_timestamp);
+    validate_filters(&slurm_nodes.nodes, cli.partition.as_deref(), &cli.state)?;
+    let start = std::time::Instant::now();
+    let matched: Result<Vec<NodeStatus>> = slurm_nodes
+        .nodes
+        .iter()
+        .filter(|&node| match cli.exclusion_reason(gres_filters, node) {
+            Some(reason) => {
+                debug!("Skipping {}: {}", node.hostname, reason);
+                false
+            }
+            None => true,
+        })
+        .map(|node| {
+            NodeStatus::from_node(
+                node,
+                &cli.gres_type,
+                job_summary.as_ref(),
+                reservation_summary.as_ref(),
+            )
+        })
+        .collect();
+    let mut statuses = matched?;
+    debug!(
+        "Processed {} nodes in {:.2?}",
+        slurm_nodes.nodes.len(),
+        start.elapsed()
+    );
+    if cli.verify_tres {
+        for node in &statuses {
+            for mismatch in node.tres_mismatches(&cli.gres_type) {
+                eprintln!("Warning: {}", mismatch);
+            }
+        }
+    }
+    if let Some(ref job_hosts) = job_hosts {
+        retain_statuses(&mut statuses, "not allocated to the job", |node| {
+            job_hosts.contains(&node.hostname)
+        });
+    }
+    if cli.hide_reserved {
+        retain_statuses(&mut statuses, "reserved", |node| !node.is_reserved());
+    }
+    if cli.exclude_powered_down {
+        retain_statuses(&mut statuses, "powered down", |node| {
+            !is_powered_down(&node.state)
+        });
+    }
+    if let Some(cpus_free) = cli.cpus_free {
+        retain_statuses(&mut statuses, "too few idle CPUs", |node| {
+            node.idle_cpus >= cpus_free
+        });
+    }
+    if let Some(free) = cli.free {
+        retain_statuses(&mut statuses, "too few idle GRES", |node| {
+            node.available_gres(cli.count_powered_down) >= free
+        });
+    }
+    if let Some(min_mem) = cli.min_mem {
+        retain_statuses(&mut statuses, "too little free memory", |node| {
+            node.free_memory >= min_mem * MB_PER_GB
+        });
+    }
+    if cli.version_mismatch_only {
+        retain_version_mismatches(&mut statuses);
+    }
+    if cli.count {
+        println!("{}", statuses.len());
+        return Ok(());
+    }
+    if let Some(key) = cli.sort {
+        sort_statuses(&mut statuses, key);
+    } else if cli.cpu_mode() {
+        sort_statuses(&mut statuses, SortKey::IdleCpus);
+    } else if cli.sort_by_model {
+        sort_statuses_by_model(&mut statuses);
+    }
+    let snapshot = cli.diff.as_deref().map(Snapshot::load).transpose()?;
+    if let Some(ref snapshot) = snapshot {
+        snapshot.compare(&mut statuses);
+    }
+    let watched: Option<Vec<WatchedStatus>> = watch
+        .as_ref()
+        .map(|_| statuses.iter().map(WatchedStatus::from_status).collect());
+    if let Some(ref watch) = watch {
+        if watch.drawn.is_some() && watch.drawn == watched {
+            return Ok(());
+        }
+        // Clear the screen and move the cursor to the top left, unless the output
+        // is piped and should only collect the tables
+        if std::io::stdout().is_terminal() {
+            print!("\x1b[2J\x1b[H");
+        }
+    }
+    let report = |nodes| Report {
+        generated_at: generated_at.clone(),
+        controller_time: controller_time.clone(),
+        cluster: slurm_nodes.meta.cluster(),
+        partition: cli.partition.as_deref(),
+        preemption: &preemption,
+        nodes,
+    };
+    // Snapshots always keep every field, as `--diff` needs them
+    if let Some(ref path) = cli.save {
+        let json = serde_json::to_string_pretty(&report(ReportNodes::Full(&statuses)))?;
+        std::fs::write(path, &json)
+            .with_context(|| format!("Writing snapshot {} failed", path.display()))?;
+    }
+    let projected = (!cli.fields.is_empty())
+        .then(|| {
+            statuses
+                .iter()
+                .map(|node| project_fields(node, &cli.fields))
+                .collect::<Result<Vec<Map<String, Value>>>>()
+        })
+        .transpose()?;
+    if cli.output == OutputFormat::Json {
+        let nodes = match projected {
+            Some(projected) => ReportNodes::Projected(projected),
+            None => ReportNodes::Full(&statuses),
+        };
+        println!("{}", serde_json::to_string_pretty(&report(nodes))?);
+        return Ok(());
+    }
+    if cli.output == OutputFormat::Ndjson {
+        match projected {
+            Some(projected) => {
+                for node in &projected {
+                    println!("{}", serde_json::to_string(node)?);
+                }
+            }
+            None => {
+                for node in &statuses {
+                    println!("{}", serde_json::to_string(node)?);
+                }
+            }
+        }
+        return Ok(());
+    }
+    if cli.total_only {
+        println!(
+            "{}",
+            GresSummary::from_statuses(&statuses, cli.count_powered_down).one_line()
+        );
+        return Ok(());
+    }
+    if cli.timestamp {
+        match controller_time {
+            Some(controller_time) => println!(
+                "Queried at {} (controller updated {})",
+                generated_at, controller_time
+            ),
+            None => println!("Queried at {}", generated_at),
+        }
+    }
+    if let Some(ref snapshot) = snapshot {
+        println!("Changes since {}", snapshot.generated_at);
+    }
+    let palette = cli.palette(config)?;
+    let display_options = DisplayOptions {
+        now,
+        palette,
+        hostname_prefix: match cli.strip_prefix {
+            Some(ref prefix) => Some(prefix.clone()),
+            None if cli.trim_common => common_hostname_prefix(&statuses),
+            None => None,
+        },
+        list_width: cli.list_width,
+        partition: cli.partition.clone(),
+        exclude_preempted: cli.exclude_preempted,
+        glyphs: {
+            let mut glyphs = Glyphs::resolve(cli, config)?;
+            if preemptible_by_me == Some(true) {
+                glyphs.preempted_color = palette.color(Color::Green);
+            }
+            glyphs
+        },
+        glyph_scale: cli.glyph_scale.map(usize::from),
+        bar_width: cli.bar.then_some(cli.bar_width.into()),
+        ascii_glyphs: cli.ascii_glyphs || !locale_is_utf8(),
+        short_state: cli.short_state,
+        ratio: cli.ratio(),
+        mem_percent: cli.mem_percent,
+        mem_precision: cli.mem_precision.into(),
+        // Icons only make sense on a terminal with a suitable font
+        icons: cli
+            .icons
+            .filter(|_| !accessible && std::io::stdout().is_terminal()),
+        accessible,
+    };
+    let tabled_nodes: Vec<TableNode> = statuses
+        .iter()
+        .map(|node| TableNode::from_status(node, &display_options))
+        .collect();
+    let mut table = Table::new(&tabled_nodes);
+    hide_unselected_columns(&cli.columns(), &mut table);
+    if cli.cpu_mode() {
+        table.with(Disable::column(ByColumnName::new("gres")));
+        table.with(Disable::column(ByColumnName::new("gres_status")));
+    }
+    let style = if accessible {
+        Some(TableStyle::Ascii)
+    } else {
+        cli.style
+    };
+    apply_style_to_table(style, &mut table);
+    if !cli.no_fit_width {
+        if let Some(width) = cli.max_width.or_else(terminal_width) {
+            fit_gres_status(&mut table, &tabled_nodes, width);
+            drop_low_priority_columns(&mut table, &tabled_nodes, width);
+            // Hostnames identify the rows, so only cut them if nothing else helps
+            truncate_long_columns(&mut table, &tabled_nodes, width);
+        }
+    }
+    // Alignment applies to column positions, so align after hiding columns
+    align_numeric_columns(&mut table);
+    // Columns are looked up by their field names, so rename them last
+    rename_ratio_headers(&mut table, cli.ratio());
+    println!("{}", table);
+    if !cli.no_legend && (cli.legend || config.legend.unwrap_or(false)) {
+        println!("{}", display_options.legend());
+    }
+    if !cli.no_caption {
+        let mut caption = time::format_local_timestamp(now);
+        if let Some(cluster) = slurm_nodes.meta.cluster() {
+            caption += &format!(" on cluster {}", cluster);
+        }
+        match cli.partition {
+            Some(ref partition) => caption += &format!(", partition {}", partition),
+            None => caption += ", all partitions",
+        }
+        // Preemption depends on the partition, which is fixed rather than the one shown
+        caption += &format!(", preemptible jobs from {}", preemption.describe());
+        match (preemptible_by_me, cli.my_partition.as_deref()) {
+            (Some(true), Some(partition)) => caption += &format!(" (preemptible by {})", partition),
+            (Some(false), Some(partition)) => {
+                caption += &format!(" (not preemptible by {})", partition)
+            }
+            _ => {}
+        }
+        println!("{}", caption);
+    }
+    if let Some(watch) = watch {
+        watch.drawn = watched;
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut cli = Cli::parse();
+    log::set_verbose(cli.verbose);
+    let config = Config::load()?;
+    // A job's nodes are shown whichever partition they are in
+    if cli.partition.is_none() && !cli.all_partitions && cli.job.is_none() {
+        cli.partition = config.partition.clone();
+    }
+    let accessible = cli.accessible(&config);
+    match cli.color {
+        _ if accessible => colored::control::set_override(false),
+        ColorChoice::Auto => {}
+        ColorChoice::Always => colored::control::set_override(true),
+        ColorChoice::Never => colored::control::set_override(false),
+    }
+    let scontrol_options = ScontrolOptions {
+        print_commands: cli.print_commands,
+        cluster: cli.cluster.clone(),
+        retries: cli.retries,
+        input_dir: cli.input_dir.clone(),
+    };
+    match cli.command {
+        Some(Command::Query {
+            ref model,
+            ref gres_type,
+            ref alias_file,
+            count,
+            ref partition,
+        }) => {
+            let gres_filters = GresFilter::parse_list(model, alias_file.as_deref())?;
+            if !run_query(
+                &scontrol_options,
+                &gres_filters,
+                gres_type,
+                count,
+                partition.as_deref(),
+            )? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Me { ref user, style }) => {
+            let user = match user {
+                Some(user) => user.clone(),
+                None => std::env::var("USER").context("Set --user, as $USER is not set")?,
+            };
+            let preemption = match cli.configured_preemption(&config) {
+                Some(preemption) => preemption,
+                None => Preemption::detect(
+                    query_partitions(&scontrol_options).ok().as_ref(),
+                    query_qos(&scontrol_options).ok().as_ref(),
+                ),
+            };
+            return run_me(&scontrol_options, &user, &preemption, style);
+        }
+        None => {}
+    }
+    if cli.interactive {
+        pick_filters(&mut cli, &scontrol_options)?;
+    }
+    let gres_filters = match cli.gres.as_deref() {
+        Some(names) => GresFilter::parse_list(names, cli.alias_file.as_deref())?,
+        None => Vec::new(),
+    };
+    if cli.dry_run {
+        for (args, condition) in cli.planned_queries(&config) {
+            let command = format_scontrol_command(&scontrol_options, args);
+            match condition {
+                Some(condition) => eprintln!("{}  # {}", command, condition),
+                None => eprintln!("{}", command),
+            }
+        }
+        return Ok(());
+    }
+    if let Some(interval) = cli.refresh_interval {
+        if cli.output != OutputFormat::Table {
+            bail!("--refresh-interval only redraws the table output");
+        }
+        let mut watch = Watch::default();
+        loop {
+            list_nodes(
+                &cli,
+                &config,
+                &scontrol_options,
+                &gres_filters,
+                Some(&mut watch),
+            )?;
+            // Overwrite the previous line if nothing was redrawn
+            if std::io::stdout().is_terminal() {
+                print!(
+                    "\r\x1b[2KLast checked {}",
+                    time::format_local_timestamp(time::now())
+                );
+                std::io::stdout().flush()?;
+            }
+            std::thread::sleep(std::time::Duration::from_secs(interval));
+        }
+    }
+    list_nodes(&cli, &config, &scontrol_options, &gres_filters, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// An idle node without GRES, with `fields` replacing the defaults
+    fn node(fields: Value) -> Node {
+        let mut node = json!({
+            "hostname": "gpu-1",
+            "state": ["IDLE"],
+            "partitions": ["gpu"],
+            "cpus": 64,
+            "alloc_idle_cpus": 64,
+            "real_memory": 512000,
+            "alloc_memory": 0,
+            "gres": "",
+            "gres_used": "",
+        });
+        if let (Value::Object(node), Value::Object(fields)) = (&mut node, fields) {
+            node.extend(fields);
+        }
+        serde_json::from_value(node).unwrap()
+    }
+
+    fn status(fields: Value) -> NodeStatus {
+        NodeStatus::from_node(&node(fields), GPU_TYPE, None, None).unwrap()
+    }
+
+    /// A running job on `gpu-1` in the `preempted` partition, with `fields` replacing
+    /// the defaults
+    fn job(fields: Value) -> Job {
+        let mut job = json!({
+            "job_id": 1,
+            "job_state": ["RUNNING"],
+            "nodes": "gpu-1",
+            "user_name": "alice",
+            "partition": PREEMPTED_PARTITION,
+            "gres_detail": [],
+        });
+        if let (Value::Object(job), Value::Object(fields)) = (&mut job, fields) {
+            job.extend(fields);
+        }
+        serde_json::from_value(job).unwrap()
+    }
+
+    fn preemption(partitions: &[&str]) -> Preemption {
+        Preemption {
+            partitions: partitions.iter().map(|&p| p.to_owned()).collect(),
+            qos: Vec::new(),
+        }
+    }
+
+    /// Status of a node with the GRES held by `jobs` in the `preempted` partition
+    fn status_with_jobs(fields: Value, jobs: &[Job]) -> NodeStatus {
+        let summary =
+            JobSummary::from_jobs(jobs, GPU_TYPE, &preemption(&[PREEMPTED_PARTITION])).unwrap();
+        NodeStatus::from_node(&node(fields), GPU_TYPE, Some(&summary), None).unwrap()
+    }
+
+    /// Nodes `gpu-1` to `gpu-N` running the given slurmd versions
+    fn versioned(versions: &[&str]) -> Vec<NodeStatus> {
+        versions
+            .iter()
+            .enumerate()
+            .map(|(i, version)| {
+                status(json!({ "hostname": format!("gpu-{}", i + 1), "version": version }))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn multi_node_job_allocations() {
+        let jobs = [job(json!({
+            "nodes": "gpu-[1-3]",
+            "gres_detail": ["gpu:a40:2(IDX:0-1)", "gpu:a40:4(IDX:0-3)", "gpu:a40:1(IDX:7)"],
+        }))];
+        let allocations = jobs[0].allocations(GPU_TYPE).unwrap();
+        let expected = [("gpu-1", 2), ("gpu-2", 4), ("gpu-3", 1)]
+            .map(|(host, count)| (host.to_owned(), "gpu:a40".to_owned(), count));
+        assert_eq!(allocations, expected);
+        let held =
+            process_preempted_jobs(&jobs, GPU_TYPE, &preemption(&[PREEMPTED_PARTITION])).unwrap();
+        for (host, count) in [("gpu-1", 2), ("gpu-2", 4), ("gpu-3", 1)] {
+            assert_eq!(
+                held[host],
+                HashMap::from([("gpu:a40".into(), count)]),
+                "{}",
+                host
+            );
+        }
+        // Nodes past the end of gres_detail got no GRES
+        let short = job(json!({
+            "nodes": "gpu-[1-3]",
+            "gres_detail": ["gpu:a40:2(IDX:0-1)", "gpu:a40:4(IDX:0-3)"],
+        }));
+        let hosts: Vec<_> = short
+            .allocations(GPU_TYPE)
+            .unwrap()
+            .into_iter()
+            .map(|(host, _, count)| (host, count))
+            .collect();
+        assert_eq!(hosts, [("gpu-1".into(), 2), ("gpu-2".into(), 4)]);
+    }
+
+    #[test]
+    fn only_running_jobs_are_preempted() {
+        let jobs = [
+            job(json!({ "job_id": 1, "gres_detail": ["gpu:a100:1(IDX:0)"] })),
+            // Older Slurm reports the state as a single string
+            job(json!({
+                "job_id": 2,
+                "job_state": "RUNNING",
+                "gres_detail": ["gpu:a100:1(IDX:1)"],
+            })),
+            job(json!({
+                "job_id": 3,
+                "job_state": ["COMPLETING"],
+                "gres_detail": ["gpu:a100:2(IDX:2-3)"],
+            })),
+            job(json!({
+                "job_id": 4,
+                "job_state": "COMPLETING",
+                "gres_detail": ["gpu:a100:2(IDX:2-3)"],
+            })),
+            job(json!({
+                "job_id": 5,
+                "job_state": ["PENDING"],
+                "gres_detail": ["gpu:a100:4(IDX:4-7)"],
+            })),
+            job(json!({
+                "job_id": 6,
+                "job_state": "PENDING",
+                "gres_detail": ["gpu:a100:4(IDX:4-7)"],
+            })),
+        ];
+        assert!(!jobs[2..].iter().any(Job::is_running));
+        let held =
+            process_preempted_jobs(&jobs, GPU_TYPE, &preemption(&[PREEMPTED_PARTITION])).unwrap();
+        assert_eq!(held["gpu-1"], HashMap::from([("gpu:a100".into(), 2)]));
+        let node = status_with_jobs(
+            json!({
+                "gres": "gpu:a100:8",
+                "gres_used": "gpu:a100:4(IDX:0-3)",
+            }),
+            &jobs,
+        );
+        assert_eq!(
+            (node.gres_used, node.gres_preempted, node.gres_idle),
+            (4, 2, 4)
+        );
+    }
+
+    #[test]
+    fn suspended_and_requeued_jobs() {
+        let jobs = [
+            // Preempted by suspension, keeping its GPUs allocated
+            job(json!({
+                "job_id": 1,
+                "job_state": ["SUSPENDED"],
+                "gres_detail": ["gpu:a100:2(IDX:0-1)"],
+            })),
+            job(json!({ "job_id": 2, "gres_detail": ["gpu:a100:1(IDX:2)"] })),
+            // Preempted by requeueing, which freed its GPUs but keeps the old gres_detail
+            job(json!({
+                "job_id": 3,
+                "job_state": ["PENDING", "REQUEUED"],
+                "gres_detail": ["gpu:a100:2(IDX:4-5)"],
+            })),
+        ];
+        let node = status_with_jobs(
+            json!({
+                "gres": "gpu:a100:8",
+                "gres_used": "gpu:a100:3(IDX:0-2)",
+            }),
+            &jobs,
+        );
+        assert_eq!(node.gres_counts(), [0, 2, 1, 5, 0, 0, 0]);
+        assert_eq!((node.gres_used, node.gres_suspended), (3, 2));
+        // Suspended jobs do not free their GPUs when preempted again
+        assert_eq!(node.effective_free, 6);
+    }
+
+    #[test]
+    fn attribute_held_to_models() {
+        let mut models = status(json!({
+            "gres": "gpu:a40:4,gpu:a100:4",
+            "gres_used": "gpu:a40:2(IDX:0-1),gpu:a100:1(IDX:0)",
+        }))
+        .models;
+        let held = HashMap::from([("gpu:a100".to_owned(), 1)]);
+        assert_eq!(attribute_held(&mut models, &held, |m| &mut m.preempted), 0);
+        assert_eq!((models[0].preempted, models[1].preempted), (0, 1));
+        // An unknown model goes to the first models with used GPUs left
+        let held = HashMap::from([("gpu".to_owned(), 1)]);
+        assert_eq!(attribute_held(&mut models, &held, |m| &mut m.suspended), 0);
+        assert_eq!((models[0].suspended, models[1].suspended), (1, 0));
+        // GPUs beyond those used are not attributed
+        let held = HashMap::from([("gpu:a100".to_owned(), 3), ("gpu".to_owned(), 2)]);
+        assert_eq!(attribute_held(&mut models, &held, |m| &mut m.preempted), 4);
+        assert_eq!((models[0].preempted, models[1].preempted), (1, 1));
+        assert_eq!(
+            models
+                .iter()
+                .map(ModelStatus::used_by_others)
+                .sum::<usize>(),
+            0
+        );
+    }
+
+    fn partitions(partitions: Value) -> SlurmPartitions {
+        serde_json::from_value(json!({ "partitions": partitions })).unwrap()
+    }
+
+    #[test]
+    fn several_preempt_partitions() {
+        let cli = Cli::parse_from([
+            "lsgres",
+            "gpu",
+            "--preempt-partition",
+            "scavenger",
+            "--preempt-partition",
+            "preemptable",
+        ]);
+        let preemption = cli.configured_preemption(&Config::default()).unwrap();
+        assert_eq!(preemption.partitions, ["scavenger", "preemptable"]);
+        let jobs = [
+            job(json!({
+                "job_id": 1,
+                "partition": "scavenger",
+                "gres_detail": ["gpu:a100:1(IDX:0)"],
+            })),
+            job(json!({
+                "job_id": 2,
+                "partition": "preemptable",
+                "gres_detail": ["gpu:a100:2(IDX:1-2)"],
+            })),
+            job(json!({
+                "job_id": 3,
+                "partition": "gpu",
+                "gres_detail": ["gpu:a100:4(IDX:3-6)"],
+            })),
+            job(json!({
+                "job_id": 4,
+                "partition": PREEMPTED_PARTITION,
+                "gres_detail": ["gpu:a100:1(IDX:7)"],
+            })),
+        ];
+        let held = process_preempted_jobs(&jobs, GPU_TYPE, &preemption).unwrap();
+        assert_eq!(held["gpu-1"], HashMap::from([("gpu:a100".into(), 3)]));
+    }
+
+    #[test]
+    fn planned_queries() {
+        let planned = |args: &[&str]| {
+            let cli = Cli::parse_from(args);
+            cli.planned_queries(&Config::default())
+                .into_iter()
+                .map(|(args, condition)| (args.join(" "), condition.is_some()))
+                .collect::<Vec<_>>()
+        };
+        // Jobs are only queried if the partitions or QOS show a preemptible one
+        assert_eq!(
+            planned(&["lsgres", "gpu"]),
+            [
+                ("show partition --json".into(), false),
+                ("show qos --json".into(), false),
+                ("show nodes --json".into(), false),
+                ("show job --json".into(), true),
+            ]
+        );
+        assert_eq!(
+            planned(&["lsgres", "gpu", "--preempt-partition", "scavenger"]),
+            [
+                ("show nodes --json".into(), false),
+                ("show job --json".into(), false),
+            ]
+        );
+        assert_eq!(
+            planned(&[
+                "lsgres",
+                "gpu",
+                "--preempt-partition",
+                "scavenger",
+                "--hide-reserved"
+            ]),
+            [
+                ("show nodes --json".into(), false),
+                ("show job --json".into(), false),
+                ("show reservations --json".into(), false),
+            ]
+        );
+    }
+
+    /// Partitions on GPU nodes shared by tiers, with a cluster-wide `REQUEUE` mode
+    fn tiered_partitions() -> Value {
+        json!([
+            {
+                "name": "gpu",
+                "priority": { "tier": 10 },
+                "preempt_mode": ["REQUEUE"],
+                "nodes": { "configured": "gpu-[1-4]", "total": 4 },
+            },
+            {
+                "name": "scavenger",
+                "priority": { "tier": 1 },
+                "preempt_mode": ["REQUEUE"],
+                "nodes": { "configured": "gpu-[1-4]", "total": 4 },
+            },
+            // No higher tier runs on these nodes to preempt its jobs
+            {
+                "name": "cpu",
+                "priority": { "tier": 1 },
+                "preempt_mode": ["REQUEUE"],
+                "nodes": { "configured": "cpu-[1-8]", "total": 8 },
+            },
+            // Older Slurm reports the tier and nodes bare
+            {
+                "name": "debug",
+                "priority_tier": 1,
+                "preempt_mode": "OFF",
+                "nodes": "gpu-[1-2]",
+            },
+        ])
+    }
+
+    #[test]
+    fn partition_based_preemption() {
+        let slurm_partitions = partitions(tiered_partitions());
+        assert_eq!(slurm_partitions.preemptible().unwrap(), ["scavenger"]);
+        // The preempted partition is kept even if no higher tier shares its nodes
+        let mut with_preempted = tiered_partitions();
+        with_preempted.as_array_mut().unwrap().push(json!({
+            "name": PREEMPTED_PARTITION,
+            "priority": { "tier": 1 },
+            "preempt_mode": ["REQUEUE"],
+            "nodes": { "configured": "gpu-5" },
+        }));
+        assert_eq!(
+            partitions(with_preempted).preemptible().unwrap(),
+            ["scavenger", PREEMPTED_PARTITION]
+        );
+        // Partitions of one tier cannot preempt each other
+        let flat = partitions(json!([
+            { "name": "a", "preempt_mode": ["SUSPEND,GANG"], "nodes": "gpu-[1-2]" },
+            { "name": "b", "preempt_mode": ["SUSPEND,GANG"], "nodes": "gpu-[1-2]" },
+        ]));
+        assert!(flat.preemptible().unwrap().is_empty());
+        // Without preempt modes, only the preempted partition is assumed
+        let unknown = partitions(json!([{ "name": "gpu" }, { "name": "scavenger" }]));
+        assert_eq!(unknown.preemptible(), None);
+        assert_eq!(
+            Preemption::detect(Some(&unknown), None).partitions,
+            [PREEMPTED_PARTITION]
+        );
+        assert_eq!(
+            Preemption::detect(None, None).partitions,
+            [PREEMPTED_PARTITION]
+        );
+    }
+
+    fn qos(qos: Value) -> SlurmQos {
+        serde_json::from_value(json!({ "qos": qos })).unwrap()
+    }
+
+    #[test]
+    fn qos_based_preemption() {
+        let slurm_qos = qos(json!([
+            { "name": "high", "preempt": { "list": ["normal", "low"], "mode": ["CLUSTER"] } },
+            { "name": "normal", "preempt": { "list": ["low"], "mode": ["OFF"] } },
+            { "name": "low", "preempt": { "list": [], "mode": ["REQUEUE"] } },
+            // A QOS listing itself does not make it preemptible
+            { "name": "gang", "preempt": { "list": ["gang"] } },
+            { "name": "interactive" },
+        ]));
+        assert_eq!(slurm_qos.preemptible(), ["normal", "low"]);
+        assert!(qos(json!([{ "name": "normal" }])).preemptible().is_empty());
+        assert!(qos(json!([])).preemptible().is_empty());
+    }
+
+    #[test]
+    fn classify_preemptible_jobs() {
+        let classify = |preemption: &Preemption| {
+            let jobs = [
+                job(json!({ "job_id": 1, "partition": "gpu", "qos": "normal" })),
+                job(json!({ "job_id": 2, "partition": "gpu", "qos": "low" })),
+                job(json!({ "job_id": 3, "partition": "scavenger", "qos": "normal" })),
+                job(json!({ "job_id": 4, "partition": "cpu", "qos": "normal" })),
+                job(json!({ "job_id": 5, "partition": PREEMPTED_PARTITION })),
+            ];
+            jobs.iter()
+                .filter(|job| preemption.is_preemptible(job))
+                .map(|job| job.job_id)
+                .collect::<Vec<_>>()
+        };
+        // Partition priority preemption
+        let by_partition = Preemption::detect(Some(&partitions(tiered_partitions())), None);
+        assert_eq!(classify(&by_partition), [3]);
+        // QOS preemption on a cluster whose partitions report no preempt modes
+        let slurm_qos = qos(json!([
+            { "name": "normal", "preempt": { "list": ["low"] } },
+            { "name": "low" },
+        ]));
+        let untiered = partitions(json!([{ "name": "gpu" }, { "name": PREEMPTED_PARTITION }]));
+        let by_qos = Preemption::detect(Some(&untiered), Some(&slurm_qos));
+        assert_eq!(by_qos.partitions, [PREEMPTED_PARTITION]);
+        assert_eq!(by_qos.qos, ["low"]);
+        assert_eq!(classify(&by_qos), [2, 5]);
+        assert!(by_qos.applies(Some(&untiered)));
+        // Both at once
+        let both = Preemption::detect(Some(&partitions(tiered_partitions())), Some(&slurm_qos));
+        assert_eq!(classify(&both), [2, 3]);
+        assert_eq!(both.describe(), "scavenger, QOS low");
+    }
+
+    #[test]
+    fn jobs_are_needed_for_existing_preemptible_partitions() {
+        let slurm_partitions = partitions(json!([{ "name": "gpu" }, { "name": "scavenger" }]));
+        assert!(preemption(&["scavenger"]).applies(Some(&slurm_partitions)));
+        assert!(!preemption(&[PREEMPTED_PARTITION]).applies(Some(&slurm_partitions)));
+        assert!(!preemption(&[]).applies(Some(&slurm_partitions)));
+        // Without partitions, any job may be preemptible
+        assert!(preemption(&[PREEMPTED_PARTITION]).applies(None));
+        let by_qos = Preemption {
+            partitions: Vec::new(),
+            qos: vec!["low".into()],
+        };
+        assert!(by_qos.applies(Some(&slurm_partitions)));
+    }
+
+    #[test]
+    fn watched_status_ignores_load() {
+        let fields = |load: u64, used: &str| {
+            json!({
+                "cpu_load": load,
+                "gres": "gpu:a100:4",
+                "gres_used": used,
+            })
+        };
+        let watched = |fields| WatchedStatus::from_status(&status(fields));
+        let drawn = watched(fields(1250, "gpu:a100:1(IDX:0)"));
+        assert_eq!(drawn, watched(fields(4000, "gpu:a100:1(IDX:0)")));
+        assert_ne!(drawn, watched(fields(1250, "gpu:a100:2(IDX:0-1)")));
+        let mut draining = fields(1250, "gpu:a100:1(IDX:0)");
+        draining["state"] = json!(["MIXED", "DRAIN"]);
+        assert_ne!(drawn, watched(draining));
+    }
+
+    #[test]
+    fn majority_version_is_most_common() {
+        let statuses = versioned(&["23.11.10", "23.02.7", "23.11.10", "24.05.1"]);
+        assert_eq!(majority_version(&statuses), Some("23.11.10"));
+    }
+
+    #[test]
+    fn majority_version_prefers_newest_on_ties() {
+        let statuses = versioned(&["23.11.9", "23.11.10", "23.02.7", "23.11.9", "23.11.10"]);
+        assert_eq!(majority_version(&statuses), Some("23.11.10"));
+    }
+
+    #[test]
+    fn version_mismatches_leave_out_majority() {
+        let mut statuses = versioned(&["23.11.10", "23.02.7", "23.11.10", "24.05.1"]);
+        retain_version_mismatches(&mut statuses);
+        let hostnames: Vec<&str> = statuses.iter().map(|n| n.hostname.as_str()).collect();
+        assert_eq!(hostnames, ["gpu-2", "gpu-4"]);
+    }
+
+    #[test]
+    fn idle_matching_counts_only_matching_models() {
+        let node = status(json!({
+            "gres": "gpu:a40:4,gpu:a100:2",
+            "gres_used": "gpu:a40:0,gpu:a100:2",
+        }));
+        let filters = |names| GresFilter::parse_list(names, None).unwrap();
+        assert_eq!(node.idle_matching(&filters("a100")), Some(0));
+        assert_eq!(node.idle_matching(&filters("A40")), Some(4));
+        assert_eq!(node.idle_matching(&filters("a40,a100")), Some(4));
+        assert_eq!(node.idle_matching(&filters("h100")), None);
+        let alias = [GresFilter::Alias(Regex::new("^gpu:a1[0-9]{2}$").unwrap())];
+        assert_eq!(node.idle_matching(&alias), Some(0));
+    }
+
+    #[test]
+    fn offline_states() {
+        let offline = |states: &[&str]| {
+            is_offline(
+                &states
+                    .iter()
+                    .map(|&s| s.to_owned())
+                    .collect::<Vec<String>>(),
+            )
+        };
+        assert!(!offline(&["IDLE"]));
+        assert!(!offline(&["MIXED"]));
+        assert!(!offline(&["ALLOCATED"]));
+        assert!(offline(&["DOWN"]));
+        assert!(offline(&["IDLE", "DOWN"]));
+        assert!(offline(&["FAIL"]));
+        assert!(offline(&["DRAINED"]));
+        // Slurm reports a drained node as drain without running jobs
+        assert!(offline(&["DRAIN"]));
+        assert!(offline(&["IDLE", "DRAIN"]));
+        // A draining node still runs jobs, and its unallocated GRES stay idle
+        assert!(!offline(&["MIXED", "DRAIN"]));
+        assert!(!offline(&["ALLOCATED", "DRAIN"]));
+        assert!(!offline(&["DRAINING"]));
+        assert!(!offline(&["IDLE", "NOT_RESPONDING"]));
+        assert!(!is_offline(&node(json!({ "state": "mixed+drain" })).state));
+        assert!(is_offline(&node(json!({ "state": "idle+drain" })).state));
+    }
+
+    fn display_options(ratio: Ratio) -> DisplayOptions {
+        let cli = Cli::parse_from(["lsgres", "gpu"]);
+        let config = Config::default();
+        DisplayOptions {
+            now: 0,
+            palette: cli.palette(&config).unwrap(),
+            hostname_prefix: None,
+            list_width: cli.list_width,
+            partition: None,
+            exclude_preempted: false,
+            glyphs: Glyphs::resolve(&cli, &config).unwrap(),
+            glyph_scale: None,
+            bar_width: None,
+            ascii_glyphs: false,
+            short_state: false,
+            ratio,
+            mem_percent: false,
+            mem_precision: cli.mem_precision.into(),
+            icons: None,
+            accessible: false,
+        }
+    }
+
+    /// Cells of the column under `header` in a rendered table
+    fn column(rendered: &str, header: &str) -> Vec<String> {
+        let mut rows = rendered
+            .lines()
+            .filter(|line| line.starts_with('|'))
+            .map(|line| {
+                line.trim_matches('|')
+                    .split('|')
+                    .map(|cell| cell.trim().to_owned())
+                    .collect::<Vec<_>>()
+            });
+        let headers = rows.next().unwrap();
+        let index = headers
+            .iter()
+            .position(|name| name == header)
+            .unwrap_or_else(|| panic!("no {} column in {:?}", header, headers));
+        rows.map(|row| row[index].clone()).collect()
+    }
+
+    /// Synthetic cluster of 2000 nodes with 8 A100s and 5000 running jobs,
+    /// every other one preemptible and every fifth one on two nodes
+    fn large_cluster() -> (SlurmNodes, SlurmJobs) {
+        const NODES: usize = 2000;
+        const JOBS: usize = 5000;
+        let nodes: Vec<Value> = (0..NODES)
+            .map(|i| {
+                json!({
+                    "hostname": format!("gpu-{}", i),
+                    "state": ["MIXED"],
+                    "partitions": ["gpu", PREEMPTED_PARTITION],
+                    "cpus": 64,
+                    "alloc_idle_cpus": 32,
+                    "real_memory": 512000,
+                    "alloc_memory": 256000,
+                    "gres": "gpu:a100:8(S:0-1)",
+                    "gres_used": "gpu:a100:4(IDX:0-3)",
+                    "tres": "cpu=64,mem=500G,gres/gpu=8,gres/gpu:a100=8",
+                })
+            })
+            .collect();
+        let jobs: Vec<Value> = (0..JOBS)
+            .map(|i| {
+                let first = i % (NODES - 1);
+                let (nodes, gres_detail) = if i % 5 == 0 {
+                    (
+                        format!("gpu-[{}-{}]", first, first + 1),
+                        json!(["gpu:a100:1(IDX:0)", "gpu:a100:1(IDX:1)"]),
+                    )
+                } else {
+                    (format!("gpu-{}", first), json!(["gpu:a100:1(IDX:2)"]))
+                };
+                json!({
+                    "job_id": i,
+                    "job_state": ["RUNNING"],
+                    "nodes": nodes,
+                    "user_name": "alice",
+                    "partition": if i % 2 == 0 { PREEMPTED_PARTITION } else { "gpu" },
+                    "gres_detail": gres_detail,
+                })
+            })
+            .collect();
+        (
+            serde_json::from_value(json!({ "nodes": nodes })).unwrap(),
+            serde_json::from_value(json!({ "jobs": jobs })).unwrap(),
+        )
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn large_cluster_is_processed_quickly() {
+        let (slurm_nodes, slurm_jobs) = large_cluster();
+        let preemption = preemption(&[PREEMPTED_PARTITION]);
+        let options = display_options(Ratio::Free);
+        let start = std::time::Instant::now();
+        let preempted = process_preempted_jobs(&slurm_jobs.jobs, GPU_TYPE, &preemption).unwrap();
+        let summary = JobSummary::from_jobs(&slurm_jobs.jobs, GPU_TYPE, &preemption).unwrap();
+        let tabled_nodes: Vec<TableNode> = slurm_nodes
+            .nodes
+            .iter()
+            .map(|node| {
+                let status = NodeStatus::from_node(node, GPU_TYPE, Some(&summary), None).unwrap();
+                TableNode::from_status(&status, &options)
+            })
+            .collect();
+        let elapsed = start.elapsed();
+        eprintln!(
+            "Processed {} nodes and {} jobs in {:.2?}",
+            slurm_nodes.nodes.len(),
+            slurm_jobs.jobs.len(),
+            elapsed
+        );
+        let held: usize = preempted.values().flat_map(HashMap::values).sum();
+        // 2500 preemptible jobs, 500 of which hold a GPU on each of two nodes
+        assert_eq!(held, 3000);
+        assert_eq!(tabled_nodes.len(), 2000);
+        // Generous enough for unoptimized builds on a loaded machine
+        assert!(
+            elapsed < std::time::Duration::from_secs(10),
+            "took {:.2?}",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn ratio_headers_match_values() {
+        colored::control::set_override(false);
+        let node = status(json!({
+            "cpus": 64,
+            "alloc_idle_cpus": 48,
+            "real_memory": 512000,
+            "alloc_memory": 128000,
+        }));
+        let render = |ratio| {
+            let tabled_nodes = [TableNode::from_status(&node, &display_options(ratio))];
+            let mut table = Table::new(tabled_nodes);
+            rename_ratio_headers(&mut table, ratio);
+            table.to_string()
+        };
+        let free = render(Ratio::Free);
+        assert_eq!(column(&free, "cpus_free"), ["48/64"]);
+        assert_eq!(column(&free, "memory_free"), ["375.0/500.0G"]);
+        let used = render(Ratio::Used);
+        assert_eq!(column(&used, "cpus_used"), ["16/64"]);
+        assert_eq!(column(&used, "memory_used"), ["125.0/500.0G"]);
+        for rendered in [&free, &used] {
+            assert!(!rendered.contains("_available"));
+        }
+    }
+
+    /// Uncolored glyphs of used, preempted and idle GRES
+    fn glyphs(used: usize, preempted: usize, idle: usize) -> String {
+        let counts = [used, 0, preempted, idle, 0, 0, 0];
+        format_gres_status(counts, None, &Glyphs::default(), false)
+    }
+
+    #[test]
+    fn gres_status_glyphs() {
+        assert_eq!(glyphs(2, 1, 0), "uup");
+        assert_eq!(glyphs(0, 0, 1), "i");
+        assert_eq!(glyphs(0, 0, 0), "");
+        assert_eq!(glyphs(1, 2, 3), "uppiii");
+        let counts = [1, 1, 1, 1, 1, 1, 1];
+        assert_eq!(
+            format_gres_status(counts, None, &Glyphs::default(), false),
+            "uspidoz"
+        );
+        // Scaled glyphs round up so that no category disappears
+        assert_eq!(
+            format_gres_status([5, 0, 1, 2, 0, 0, 0], Some(4), &Glyphs::default(), false),
+            "uupi(×4)"
+        );
+    }
+
+    #[test]
+    fn every_mapped_state_has_a_style() {
+        let named = [
+            "DOWN",
+            "FAIL",
+            "INVAL",
+            "NOT_RESPONDING",
+            "DRAINED",
+            "DRAIN",
+            "DRAINING",
+            "REBOOT_ISSUED",
+            "REBOOT_REQUESTED",
+            "POWERED_DOWN",
+            "POWERING_UP",
+            "POWERING_DOWN",
+            "POWER_DOWN",
+            "FUTURE",
+            "DYNAMIC_FUTURE",
+            "UNKNOWN",
+            "ALLOCATED",
+            "MIXED",
+            "COMPLETING",
+            "IDLE",
+            "BLOCKED",
+            "MAINTENANCE",
+            "RESERVED",
+            "PLANNED",
+            "CLOUD",
+            "DYNAMIC_NORM",
+        ];
+        let mapped: Vec<_> = STATE_STYLES.iter().map(|&(name, _, _)| name).collect();
+        assert_eq!(mapped, named);
+        let mut abbreviations = BTreeSet::new();
+        for (rank, &(name, short, color)) in STATE_STYLES.iter().enumerate() {
+            assert_eq!(state_style(name), (short, color));
+            assert_eq!(state_rank(name), rank);
+            assert!(
+                abbreviations.insert(short),
+                "{} is abbreviated twice",
+                short
+            );
+        }
+        for state in [
+            "COMPLETING",
+            "PLANNED",
+            "RESERVED",
+            "MAINTENANCE",
+            "FAIL",
+            "INVAL",
+        ] {
+            assert!(state_style(state).1.is_some(), "{} has no color", state);
+        }
+        assert_eq!(state_style("POWERING_DOWN"), ("PWDN", Some(Color::Cyan)));
+    }
+
+    #[test]
+    fn unknown_states_are_unstyled() {
+        assert_eq!(state_style("HIBERNATING"), ("HIBERNATING", None));
+        assert_eq!(state_style(""), ("", None));
+        assert_eq!(state_rank("HIBERNATING"), STATE_STYLES.len());
+        let states = ["HIBERNATING".to_owned(), "MIXED".to_owned()];
+        assert_eq!(dominant_state(&states), Some("MIXED"));
+        assert_eq!(dominant_state(&states[..1]), Some("HIBERNATING"));
+        assert_eq!(dominant_state(&[]), None);
+    }
+
+    #[test]
+    fn powered_down_states() {
+        let powered_down = |states: &[&str]| {
+            is_powered_down(
+                &states
+                    .iter()
+                    .map(|&s| s.to_owned())
+                    .collect::<Vec<String>>(),
+            )
+        };
+        assert!(!powered_down(&["IDLE"]));
+        assert!(!powered_down(&["MIXED", "POWER_DOWN"]));
+        assert!(powered_down(&["IDLE", "POWERED_DOWN"]));
+        assert!(powered_down(&["IDLE", "POWERING_DOWN"]));
+        // A booting node is neither off nor usable yet
+        assert!(powered_down(&["IDLE", "POWERING_UP"]));
+        assert!(is_powered_down(
+            &node(json!({ "state": "idle+powering_up" })).state
+        ));
+    }
+
+    #[test]
+    fn powered_down_gres_are_available_after_boot() {
+        for state in ["POWERED_DOWN", "POWERING_UP"] {
+            let node = status(json!({
+                "state": ["IDLE", state],
+                "gres": "gpu:a100:4",
+                "gres_used": "gpu:a100:0(IDX:N/A)",
+            }));
+            assert_eq!((node.gres_idle, node.gres_powered_down), (0, 4));
+            assert_eq!(node.effective_free, 0);
+            assert_eq!(node.available_gres(false), 0);
+            assert_eq!(node.available_gres(true), 4);
+        }
+        let node = status(json!({ "gres": "gpu:a100:4", "gres_used": "gpu:a100:1(IDX:0)" }));
+        assert_eq!(node.available_gres(false), 3);
+        assert_eq!(node.available_gres(true), 3);
+    }
+
+    #[test]
+    fn offline_nodes_have_no_effective_free() {
+        let jobs = [job(json!({ "gres_detail": ["gpu:a100:2(IDX:0-1)"] }))];
+        let fields = |state| {
+            json!({
+                "state": state,
+                "gres": "gpu:a100:4",
+                "gres_used": "gpu:a100:2(IDX:0-1)",
+            })
+        };
+        let mixed = status_with_jobs(fields(json!(["MIXED"])), &jobs);
+        assert_eq!((mixed.gres_preempted, mixed.gres_idle), (2, 2));
+        assert_eq!(mixed.effective_free, 4);
+        let draining = status_with_jobs(fields(json!(["MIXED", "DRAIN"])), &jobs);
+        assert_eq!(draining.effective_free, 4);
+        for state in [
+            json!(["MIXED", "DOWN"]),
+            json!(["DRAINED"]),
+            json!(["FAIL"]),
+        ] {
+            let offline = status_with_jobs(fields(state), &jobs);
+            assert_eq!((offline.gres_preempted, offline.gres_offline), (2, 2));
+            assert_eq!(offline.effective_free, 0);
+        }
+        let powered_down = status_with_jobs(fields(json!(["POWERING_DOWN"])), &jobs);
+        assert_eq!(powered_down.effective_free, 0);
+    }
+
+    fn parse_gres(s: &str) -> (String, usize, Option<String>) {
+        let status = GresStatus::from_str(s).unwrap();
+        let indices = status.indices.map(|indices| indices.to_string());
+        (status.model, status.count, indices)
+    }
+
+    #[test]
+    fn gres_from_str() {
+        assert_eq!(parse_gres(""), (String::new(), 0, None));
+        assert_eq!(parse_gres("(null)"), (String::new(), 0, None));
+        assert_eq!(parse_gres("gpu:a100:4"), ("gpu:a100".into(), 4, None));
+        assert_eq!(parse_gres("gpu:a100:0"), ("gpu:a100".into(), 0, None));
+        assert_eq!(
+            parse_gres("gpu:a100:4(IDX:0-3)"),
+            ("gpu:a100".into(), 4, Some("0-3".into()))
+        );
+        // Clusters without GPU types report the type alone
+        assert_eq!(parse_gres("gpu:4"), ("gpu".into(), 4, None));
+        assert!(GresStatus::from_str("gpu:a100").is_err());
+        assert!(GresStatus::from_str("gpu:a100:4(IDX:0-3").is_err());
+    }
+
+    fn allocation(detail: &str) -> Option<(String, usize)> {
+        parse_allocation(detail, GPU_TYPE)
+    }
+
+    #[test]
+    fn gpu_index_counts() {
+        for (indices, count) in [
+            ("0", 1),
+            ("0-3", 4),
+            ("0-1,3", 3),
+            ("0,2,4-7", 6),
+            (" 1 , 3-4 ", 3),
+            ("5-5", 1),
+            ("0-4095", 4096),
+        ] {
+            assert_eq!(count_gpu_indices(indices).unwrap(), count, "{}", indices);
+        }
+        for indices in [
+            "7-2",
+            "3-",
+            "-3",
+            "0-4294967295",
+            "0-18446744073709551616",
+            "0-4096",
+            "0-4000,4001-4100",
+            "",
+            ",",
+            "0,,1",
+            "0,",
+            "a",
+            "0-b",
+            "1-2-3",
+            "N/A",
+        ] {
+            assert!(
+                count_gpu_indices(indices).is_err(),
+                "{:?} was accepted",
+                indices
+            );
+        }
+        assert!(IndexSet::parse("0-4294967295").is_err());
+        assert_eq!(IndexSet::parse("0-4095").unwrap().count(), MAX_INDICES);
+        // Bad entries are skipped rather than miscounted
+        assert_eq!(allocation("gpu:a100:2(IDX:7-2)"), None);
+        assert_eq!(allocation("gpu:a100:2(IDX:0-4294967295)"), None);
+    }
+
+    #[test]
+    fn random_indices_never_panic() {
+        // A fixed linear congruential generator keeps failures reproducible
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: usize| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) as usize % bound
+        };
+        let alphabet: Vec<char> = "0123456789--,,:()IDX/ ".chars().collect();
+        for _ in 0..20_000 {
+            let len = next(16);
+            let indices: String = (0..len).map(|_| alphabet[next(alphabet.len())]).collect();
+            if let Ok(count) = count_gpu_indices(&indices) {
+                assert!(count <= MAX_INDICES, "{:?} counted {}", indices, count);
+            }
+            let _ = parse_allocation(&format!("gpu:a100:2(IDX:{})", indices), GPU_TYPE);
+            let _ = GresStatus::parse_list(&format!("gpu:a100:{}", indices), GPU_TYPE);
+            if let Ok(set) = IndexSet::parse(&indices) {
+                assert!(set.count() <= MAX_INDICES, "{:?} parsed {}", indices, set);
+            }
+        }
+    }
+
+    #[test]
+    fn typed_and_typeless_gres() {
+        for (gres, model, count) in [
+            ("gpu:8", "gpu", 8),
+            ("gpu:4(IDX:0-3)", "gpu", 4),
+            ("gpu:a100:8", "gpu:a100", 8),
+            ("gpu:a100:4(IDX:0-3)", "gpu:a100", 4),
+        ] {
+            assert_eq!(parse_gres(gres).0, model, "{}", gres);
+            assert_eq!(parse_gres(gres).1, count, "{}", gres);
+            assert_eq!(
+                allocation(gres),
+                Some((model.to_owned(), count)),
+                "{}",
+                gres
+            );
+        }
+        assert_eq!(allocation("gpu:2(IDX:0,3)"), Some(("gpu".into(), 2)));
+        assert_eq!(allocation("shard:4"), None);
+    }
+
+    #[test]
+    fn socket_and_index_suffixes() {
+        let suffixes = |gres: &str| {
+            let status = GresStatus::from_str(gres).unwrap();
+            let to_string = |set: Option<IndexSet>| set.map(|set| set.to_string());
+            (
+                status.count,
+                to_string(status.indices),
+                to_string(status.sockets),
+            )
+        };
+        let some = |s: &str| Some(s.to_owned());
+        assert_eq!(suffixes("gpu:a100:4(S:0-1)"), (4, None, some("0-1")));
+        assert_eq!(suffixes("gpu:a100:4(IDX:0-3)"), (4, some("0-3"), None));
+        assert_eq!(
+            suffixes("gpu:a100:2(S:0)(IDX:0-1)"),
+            (2, some("0-1"), some("0"))
+        );
+        assert_eq!(
+            suffixes("gpu:a100:2(IDX:0-1)(S:0)"),
+            (2, some("0-1"), some("0"))
+        );
+        for detail in [
+            "gpu:a100:2(S:0-1)",
+            "gpu:a100:2(IDX:2-3)",
+            "gpu:a100:2(S:0)(IDX:2-3)",
+            "gpu:a100:2(IDX:2-3)(S:0)",
+        ] {
+            assert_eq!(
+                allocation(detail),
+                Some(("gpu:a100".into(), 2)),
+                "{}",
+                detail
+            );
+        }
+        // The index list wins over a count that disagrees with it, whatever the order
+        assert_eq!(
+            allocation("gpu:a100:4(S:1)(IDX:5)"),
+            Some(("gpu:a100".into(), 1))
+        );
+    }
+
+    #[test]
+    fn typeless_gres_of_preemptible_jobs() {
+        let jobs = [job(json!({ "gres_detail": ["gpu:4(IDX:0-3)"] }))];
+        let node = status_with_jobs(
+            json!({ "gres": "gpu:8", "gres_used": "gpu:4(IDX:0-3)" }),
+            &jobs,
+        );
+        assert_eq!(node.models.len(), 1);
+        assert_eq!(node.models[0].model, "gpu");
+        assert_eq!(
+            (node.gres_used, node.gres_preempted, node.gres_idle),
+            (4, 4, 4)
+        );
+        assert_eq!(node.effective_free, 8);
+    }
+
+    #[test]
+    fn gres_list_with_several_models() {
+        let statuses = GresStatus::parse_list("gpu:a40:2,gpu:a100:2", GPU_TYPE);
+        let models: Vec<_> = statuses
+            .iter()
+            .map(|status| (status.model.as_str(), status.count))
+            .collect();
+        assert_eq!(models, [("gpu:a40", 2), ("gpu:a100", 2)]);
+        // A malformed entry does not hide the others
+        let statuses = GresStatus::parse_list("gpu:a40,gpu:a100:2(IDX:0-1)", GPU_TYPE);
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].model, "gpu:a100");
+    }
+
+    /// Model, total, used and idle GRES of each model
+    fn model_counts(node: &NodeStatus) -> Vec<(String, usize, usize, usize)> {
+        node.models
+            .iter()
+            .map(|model| (model.model.clone(), model.total, model.used, model.idle))
+            .collect()
+    }
+
+    #[test]
+    fn gres_from_tres() {
+        let node = status(json!({
+            "tres": "cpu=64,mem=512000M,gres/gpu=8",
+            "tres_used": "cpu=16,mem=128000M,gres/gpu=3",
+        }));
+        assert_eq!(model_counts(&node), [("gpu".into(), 8, 3, 5)]);
+        assert_eq!((node.gres_total, node.gres_used, node.gres_idle), (8, 3, 5));
+        // Nothing is used yet when tres_used lacks the GRES
+        let node = status(json!({
+            "tres": "cpu=64,mem=512000M,gres/gpu=8",
+            "tres_used": "cpu=16,mem=128000M",
+        }));
+        assert_eq!(model_counts(&node), [("gpu".into(), 8, 0, 8)]);
+        // Per-model entries are not the type's total
+        assert_eq!(tres_gres_count("gres/gpu:a100=4", GPU_TYPE), None);
+        assert_eq!(
+            tres_gres_count("cpu=64,gres/gpu=8,gres/gpu:a100=8", GPU_TYPE),
+            Some(8)
+        );
+    }
+
+    #[test]
+    fn tres_mismatches_of_type_and_models() {
+        let node = status(json!({
+            "gres": "gpu:a100:4",
+            "gres_used": "gpu:a100:1(IDX:0)",
+            "tres": "cpu=64,gres/gpu=4,gres/gpu:a100=4",
+            "tres_used": "cpu=16,gres/gpu=1,gres/gpu:a100=1",
+        }));
+        assert!(node.tres_mismatches(GPU_TYPE).is_empty());
+        let node = status(json!({
+            "gres": "gpu:a100:4",
+            "gres_used": "gpu:a100:1(IDX:0)",
+            "tres": "cpu=64,gres/gpu=8,gres/gpu:a100=4",
+            "tres_used": "cpu=16,gres/gpu=3,gres/gpu:a100=2",
+        }));
+        assert_eq!(
+            node.tres_mismatches(GPU_TYPE),
+            [
+                "gpu-1 reports 8 gpu in tres but 4 in gres",
+                "gpu-1 reports 3 used gpu in tres_used but 1 in gres_used",
+                "gpu-1 reports 2 used gpu:a100 in tres_used but 1 in gres_used",
+            ]
+        );
+    }
+
+    #[test]
+    fn gres_without_tres() {
+        let node = status(json!({ "tres": "cpu=64,mem=512000M,billing=64" }));
+        assert!(node.models.is_empty());
+        assert_eq!(node.gres_total, 0);
+        let node = status(json!({ "tres": "cpu=64,gres/fpga=2" }));
+        assert_eq!(node.gres_total, 0);
+    }
+
+    #[test]
+    fn gres_wins_over_tres() {
+        let node = status(json!({
+            "gres": "gpu:a100:4",
+            "gres_used": "gpu:a100:1(IDX:0)",
+            "tres": "cpu=64,mem=512000M,gres/gpu=8",
+            "tres_used": "cpu=16,mem=128000M,gres/gpu=3",
+        }));
+        assert_eq!(model_counts(&node), [("gpu:a100".into(), 4, 1, 3)]);
+    }
+
+    #[test]
+    fn vendor_neutral_models() {
+        for (gres, name) in [
+            ("gpu:mi250:8", "mi250"),
+            ("gpu:mi300x:8(IDX:0-7)", "mi300x"),
+            ("gpu:max1550:4(S:0-1)", "max1550"),
+        ] {
+            let status = GresStatus::from_str(gres).unwrap();
+            assert_eq!(model_name(&status.model), name);
+            assert!(!is_mig_model(&status.model), "{}", gres);
+            let filter = GresFilter::parse_list(name, None).unwrap();
+            assert!(filter[0].matches(gres), "{} does not match {}", name, gres);
+            let upper = GresFilter::parse_list(&name.to_uppercase(), None).unwrap();
+            assert!(upper[0].matches(gres), "{} does not match {}", name, gres);
+            assert_eq!(allocation(gres).unwrap().0, format!("gpu:{}", name));
+        }
+        let mi300x = GresFilter::parse_list("mi300x", None).unwrap();
+        assert!(!mi300x[0].matches("gpu:mi250:8"));
+        assert!(!mi300x[0].matches("gpu:max1550:4"));
+        let mi250 = GresFilter::parse_list("mi250", None).unwrap();
+        assert!(!mi250[0].matches("gpu:mi300x:8"));
+        let node = status(json!({
+            "gres": "gpu:mi250:8,gpu:max1550:4",
+            "gres_used": "gpu:mi250:2(IDX:0-1),gpu:max1550:4(IDX:0-3)",
+        }));
+        assert_eq!((node.gres_total, node.gres_idle), (12, 6));
+    }
+
+    #[test]
+    fn fpga_type() {
+        let cli = Cli::parse_from(["lsgres", "fpga", "--type", "fpga"]);
+        let filters = GresFilter::parse_list("fpga", None).unwrap();
+        let fpga_only = node(json!({
+            "gres": "fpga:agilex:2",
+            "gres_used": "fpga:agilex:1(IDX:0)",
+        }));
+        let mixed = node(json!({
+            "gres": "gpu:a100:4,fpga:agilex:2",
+            "gres_used": "gpu:a100:4(IDX:0-3),fpga:agilex:0(IDX:N/A)",
+        }));
+        let gpu_only = node(json!({ "gres": "gpu:a100:4", "gres_used": "gpu:a100:0" }));
+        assert_eq!(cli.exclusion_reason(&filters, &fpga_only), None);
+        assert_eq!(cli.exclusion_reason(&filters, &mixed), None);
+        assert!(cli.exclusion_reason(&filters, &gpu_only).is_some());
+        // A GPU model does not select nodes when counting FPGAs
+        let a100 = GresFilter::parse_list("a100", None).unwrap();
+        assert!(cli.exclusion_reason(&a100, &mixed).is_some());
+
+        let models = |node: &Node| {
+            let status = NodeStatus::from_node(node, "fpga", None, None).unwrap();
+            let models: Vec<_> = status
+                .models
+                .iter()
+                .map(|model| (model.model.clone(), model.total, model.idle))
+                .collect();
+            models
+        };
+        assert_eq!(models(&fpga_only), [("fpga:agilex".into(), 2, 1)]);
+        assert_eq!(models(&mixed), [("fpga:agilex".into(), 2, 2)]);
+        assert!(models(&gpu_only).is_empty());
+
+        // Preemptible jobs only hold GRES of the selected type
+        let jobs = [job(json!({
+            "gres_detail": ["gpu:a100:2(IDX:0-1),fpga:agilex:1(IDX:0)"],
+        }))];
+        let preemption = preemption(&[PREEMPTED_PARTITION]);
+        let held = |gres_type| process_preempted_jobs(&jobs, gres_type, &preemption).unwrap();
+        assert_eq!(
+            held("fpga")["gpu-1"],
+            HashMap::from([("fpga:agilex".into(), 1)])
+        );
+        assert_eq!(
+            held(GPU_TYPE)["gpu-1"],
+            HashMap::from([("gpu:a100".into(), 2)])
+        );
+    }
+
+    #[test]
+    fn gres_list_with_shards() {
+        let gres = "gpu:a40:4(S:0),gpu:a100:2(S:1),shard:16(S:0-1)";
+        let models = |gres_type| {
+            GresStatus::parse_list(gres, gres_type)
+                .into_iter()
+                .map(|status| (status.model, status.count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            models(GPU_TYPE),
+            [("gpu:a40".into(), 4), ("gpu:a100".into(), 2)]
+        );
+        assert_eq!(models(SHARD_TYPE), [("shard".into(), 16)]);
+        assert!(models("fpga").is_empty());
+        let node = status(json!({
+            "gres": gres,
+            "gres_used": "gpu:a40:1(IDX:0),gpu:a100:2(IDX:0-1),shard:4(4/16,0/16)",
+        }));
+        let counts: Vec<_> = node
+            .models
+            .iter()
+            .map(|model| (model.model.as_str(), model.total, model.used, model.idle))
+            .collect();
+        assert_eq!(counts, [("gpu:a40", 4, 1, 3), ("gpu:a100", 2, 2, 0)]);
+        assert_eq!((node.gres_total, node.gres_used, node.gres_idle), (6, 3, 3));
+        assert_eq!((node.shards_used, node.shards), (Some(4), Some(16)));
+    }
+
+    /// Used, drained and idle GRES of a node with 4 A100s
+    fn drain_counts(gres_used: &str, gres_drain: &str) -> (usize, usize, usize) {
+        let node = status(json!({
+            "gres": "gpu:a100:4",
+            "gres_used": gres_used,
+            "gres_drain": gres_drain,
+        }));
+        (node.gres_used, node.gres_drained, node.gres_idle)
+    }
+
+    #[test]
+    fn gres_drain_with_indices() {
+        assert_eq!(
+            drain_counts("gpu:a100:1(IDX:0)", "gpu:a100:2(IDX:2-3)"),
+            (1, 2, 1)
+        );
+        // Index 0 is in use, so only index 2 is drained while unallocated
+        assert_eq!(
+            drain_counts("gpu:a100:1(IDX:0)", "gpu:a100:2(IDX:0,2)"),
+            (1, 1, 2)
+        );
+        let node = status(json!({
+            "gres": "gpu:a100:4",
+            "gres_used": "gpu:a100:1(IDX:0)",
+            "gres_drain": "gpu:a100:2(IDX:0,2)",
+        }));
+        assert_eq!(node.idle_indices.unwrap().to_string(), "1,3");
+        assert_eq!(
+            drain_counts("gpu:a100:4(IDX:0-3)", "gpu:a100:4(IDX:0-3)"),
+            (4, 0, 0)
+        );
+        // Used GRES without usable indices still keep the drained ones from exceeding the rest
+        assert_eq!(
+            drain_counts("gpu:a100:2(IDX:N/A)", "gpu:a100:4(IDX:0-3)"),
+            (2, 2, 0)
+        );
+        assert_eq!(
+            drain_counts("gpu:a100:2(IDX:6-7)", "gpu:a100:4(IDX:0-3)"),
+            (2, 2, 0)
+        );
+        assert_eq!(
+            drain_counts("gpu:a100:2(IDX:3-4)", "gpu:a100:2(IDX:0-1)"),
+            (2, 2, 0)
+        );
+    }
+
+    #[test]
+    fn gres_drain_with_count() {
+        assert_eq!(drain_counts("gpu:a100:1(IDX:0)", "gpu:a100:2"), (1, 2, 1));
+        // The drained count overlaps used GRES, leaving only one unallocated to drain
+        assert_eq!(drain_counts("gpu:a100:3(IDX:0-2)", "gpu:a100:2"), (3, 1, 0));
+        assert_eq!(drain_counts("gpu:a100:4(IDX:0-3)", "gpu:a100:4"), (4, 0, 0));
+        assert_eq!(drain_counts("gpu:a100:1", "gpu:a100:2(IDX:0-1)"), (1, 2, 1));
+        assert_eq!(drain_counts("gpu:a100:1(IDX:0)", "N/A"), (1, 0, 3));
+    }
+
+    #[test]
+    fn memory_overallocated() {
+        let node = status(json!({ "real_memory": 256000, "alloc_memory": 300000 }));
+        assert_eq!((node.memory, node.free_memory), (256000, 0));
+        let options = display_options(Ratio::Free);
+        colored::control::set_override(false);
+        assert_eq!(
+            TableNode::from_status(&node, &options).memory_available,
+            "0.0/250.0G"
+        );
+        // The warning is only printed for the first listing of a node
+        let hostname = "overallocated-1";
+        status(json!({ "hostname": hostname, "real_memory": 1000, "alloc_memory": 2000 }));
+        assert!(!OVERALLOCATED_NODES
+            .lock()
+            .unwrap()
+            .insert(hostname.to_owned()));
+    }
+
+    #[test]
+    fn memory_excludes_spec_limit() {
+        let node = status(json!({
+            "real_memory": 512000,
+            "alloc_memory": 128000,
+            "mem_spec_limit": 16000,
+        }));
+        assert_eq!((node.memory, node.free_memory), (496000, 368000));
+        // Also reported wrapped like other numbers
+        let node = status(json!({
+            "real_memory": 512000,
+            "alloc_memory": 500000,
+            "mem_spec_limit": { "set": true, "infinite": false, "number": 16000 },
+        }));
+        assert_eq!((node.memory, node.free_memory), (496000, 0));
+        let node = status(json!({ "real_memory": 512000, "alloc_memory": 0 }));
+        assert_eq!((node.memory, node.free_memory), (512000, 512000));
+    }
+
+    #[test]
+    fn columns_to_drop_by_width() {
+        let widths = [("state", 20), ("memory_available", 18)];
+        assert!(columns_to_drop(120, &widths, 130).is_empty());
+        assert!(columns_to_drop(120, &widths, 120).is_empty());
+        assert_eq!(columns_to_drop(120, &widths, 110), ["state"]);
+        assert_eq!(columns_to_drop(120, &widths, 100), ["state"]);
+        assert_eq!(
+            columns_to_drop(120, &widths, 99),
+            ["state", "memory_available"]
+        );
+        // Columns are only dropped in priority order, even when none is enough
+        assert_eq!(
+            columns_to_drop(120, &widths, 40),
+            ["state", "memory_available"]
+        );
+    }
+
+    #[test]
+    fn scaled_and_wrapped_cpu_load() {
+        colored::control::set_override(false);
+        let load = |cpu_load: Value| status(json!({ "cpu_load": cpu_load })).cpu_load;
+        // Slurm reports the load multiplied by 100
+        assert_eq!(load(json!(1234)), Some(12.34));
+        assert_eq!(
+            load(json!({ "set": true, "infinite": false, "number": 250 })),
+            Some(2.5)
+        );
+        assert_eq!(
+            load(json!({ "set": false, "infinite": false, "number": 0 })),
+            None
+        );
+        assert_eq!(
+            load(json!({ "set": true, "infinite": true, "number": 0 })),
+            None
+        );
+        assert_eq!(load(json!(NO_VAL)), None);
+        assert_eq!(status(json!({})).cpu_load, None);
+        let node = status(json!({
+            "cpus": { "set": true, "infinite": false, "number": 128 },
+            "alloc_idle_cpus": { "set": false, "infinite": false, "number": 0 },
+        }));
+        assert_eq!((node.cpus, node.idle_cpus), (128, 0));
+        let render = |cpu_load: Value| {
+            let node = status(json!({ "cpu_load": cpu_load }));
+            let table = Table::new([TableNode::from_status(&node, &display_options(Ratio::Free))]);
+            column(&table.to_string(), "load")
+        };
+        assert_eq!(render(json!(1234)), ["12.3"]);
+        assert_eq!(
+            render(json!({ "set": false, "infinite": false, "number": 0 })),
+            ["-"]
+        );
+        assert_eq!(format_load(Some(80.0), 64), "80.0");
+        assert_eq!(format_load(None, 64), "-");
+    }
+
+    #[test]
+    fn running_jobs_on_each_node() {
+        let jobs = [
+            job(json!({ "job_id": 1, "nodes": "gpu-[1-3]", "user_name": "alice" })),
+            job(json!({ "job_id": 2, "nodes": "gpu-2", "user_name": "bob" })),
+            job(json!({ "job_id": 3, "nodes": "gpu-3", "job_state": ["PENDING"] })),
+            job(json!({ "job_id": 4, "nodes": "gpu-4", "job_state": ["COMPLETING"] })),
+        ];
+        let summary = JobSummary::from_jobs(&jobs, GPU_TYPE, &preemption(&[])).unwrap();
+        let counts: BTreeMap<_, _> = summary
+            .running_jobs
+            .iter()
+            .map(|(host, &count)| (host.as_str(), count))
+            .collect();
+        assert_eq!(
+            counts,
+            BTreeMap::from([("gpu-1", 1), ("gpu-2", 2), ("gpu-3", 1)])
+        );
+        assert_eq!(
+            summary.users["gpu-2"],
+            BTreeSet::from(["alice".to_owned(), "bob".to_owned()])
+        );
+        // Nodes without running jobs show 0 rather than nothing
+        let jobs_on = |hostname: &str| {
+            NodeStatus::from_node(
+                &node(json!({ "hostname": hostname })),
+                GPU_TYPE,
+                Some(&summary),
+                None,
+            )
+            .unwrap()
+            .running_jobs
+        };
+        assert_eq!(jobs_on("gpu-2"), Some(2));
+        assert_eq!(jobs_on("gpu-4"), Some(0));
+    }
+
+    #[test]
+    fn gres_used_indices_win_over_count() {
+        // Some Slurm versions report a count that disagrees with the index list
+        assert_eq!(
+            parse_gres("gpu:a100:4(IDX:1,3)"),
+            ("gpu:a100".into(), 2, Some("1,3".into()))
+        );
+        assert_eq!(
+            parse_gres("gpu:a100:1(IDX:0-2,5)"),
+            ("gpu:a100".into(), 4, Some("0-2,5".into()))
+        );
+        // Agreeing counts are unchanged
+        assert_eq!(
+            parse_gres("gpu:a100:2(IDX:1,3)"),
+            ("gpu:a100".into(), 2, Some("1,3".into()))
+        );
+        let node = status(json!({
+            "gres": "gpu:a100:8(S:0-1)",
+            "gres_used": "gpu:a100:6(IDX:0-2)",
+        }));
+        assert_eq!((node.gres_used, node.gres_idle), (3, 5));
+    }
+
+    #[test]
+    fn bar_cells_add_up_to_width() {
+        assert_eq!(scale_to_width([1, 0, 2], 10), [3, 0, 7]);
+        assert_eq!(scale_to_width([2, 0, 5], 10), [3, 0, 7]);
+        assert_eq!(scale_to_width([1, 1, 1], 10), [4, 3, 3]);
+        assert_eq!(scale_to_width([0, 0, 0], 10), [0, 0, 0]);
+        for total in 1..=17 {
+            for used in 0..=total {
+                for preempted in 0..=total - used {
+                    let counts = [used, preempted, total - used - preempted];
+                    for width in 1..=16 {
+                        let cells = scale_to_width(counts, width);
+                        assert_eq!(cells.iter().sum::<usize>(), width, "{:?}", counts);
+                    }
+                }
+            }
+        }
+        colored::control::set_override(false);
+        let bar = |used, preempted, idle| {
+            let counts = [used, 0, preempted, idle, 0, 0, 0];
+            format_gres_bar(counts, 10, true, &Glyphs::default())
+        };
+        assert_eq!(bar(1, 0, 2), "###.......");
+        assert_eq!(bar(2, 0, 5), "###.......");
+        assert_eq!(bar(1, 1, 1), "####%%%...");
+        assert_eq!(bar(0, 0, 0), "-");
+        assert_eq!(
+            format_gres_bar([1, 0, 0, 2, 0, 0, 0], 10, false, &Glyphs::default()),
+            "███░░░░░░░"
+        );
+    }
+
+    #[test]
+    fn legend_reflects_overridden_glyphs() {
+        colored::control::set_override(false);
+        let mut options = display_options(Ratio::Free);
+        assert!(options
+            .legend()
+            .starts_with("u used  s suspended  p preempted  i idle"));
+        let cli = Cli::parse_from(["lsgres", "gpu", "--glyph-used", "#"]);
+        let config = Config {
+            glyph_idle: Some("·".into()),
+            glyph_used: Some("U".into()),
+            ..Config::default()
+        };
+        options.glyphs = Glyphs::resolve(&cli, &config).unwrap();
+        let legend = options.legend();
+        // The command line takes precedence over the config file
+        assert!(legend.starts_with("# used  s suspended  p preempted  · idle"));
+        let counts = [1, 0, 0, 2, 0, 0, 0];
+        assert_eq!(
+            format_gres_status(counts, None, &options.glyphs, false),
+            "#··"
+        );
+        options.bar_width = Some(10);
+        options.ascii_glyphs = true;
+        assert!(options
+            .legend()
+            .starts_with("# used  = suspended  % preempted  . idle"));
+    }
+
+    #[test]
+    fn dense_nodes_are_scaled_to_fit() {
+        /// The glyphs and the scale chosen for them
+        fn scaled(counts: [usize; CATEGORIES]) -> (String, usize) {
+            let rendered = format_gres_status(counts, None, &Glyphs::default(), false);
+            match rendered.split_once("(×") {
+                Some((glyphs, scale)) => (
+                    glyphs.to_owned(),
+                    scale.trim_end_matches(')').parse().unwrap(),
+                ),
+                None => (rendered, 1),
+            }
+        }
+        let (glyphs, scale) = scaled([60, 0, 1, 3, 0, 0, 0]);
+        assert_eq!(scale, 3);
+        assert_eq!(glyphs, format!("{}pi", "u".repeat(20)));
+        let (glyphs, scale) = scaled([0, 0, 1, 127, 0, 0, 0]);
+        assert_eq!(scale, 5);
+        assert_eq!(glyphs, format!("p{}", "i".repeat(26)));
+        // Nodes that fit are not scaled
+        assert_eq!(scaled([8, 0, 4, 14, 0, 0, 0]).1, 1);
+        for total in [64, 128] {
+            for used in 0..total {
+                for preempted in [0, 1, total - used] {
+                    let counts = [used, 0, preempted, total - used - preempted, 0, 0, 0];
+                    let (glyphs, scale) = scaled(counts);
+                    assert!(scale > 1, "{:?}", counts);
+                    assert!(glyphs.chars().count() <= MAX_GLYPHS, "{:?}", counts);
+                    assert_eq!(glyphs.contains('p'), counts[2] > 0, "{:?}", counts);
+                }
+            }
+            // Every category shows even with a single GRES in it
+            let (glyphs, _) = scaled([total - 6, 1, 1, 1, 1, 1, 1]);
+            assert!(glyphs.ends_with("spidoz"), "{}", glyphs);
+            assert!(glyphs.chars().count() <= MAX_GLYPHS);
+        }
+    }
+
+    #[test]
+    fn dominant_of_multiple_states() {
+        colored::control::set_override(false);
+        let states = |states: &[&str]| -> Vec<String> {
+            states.iter().map(|&state| state.to_owned()).collect()
+        };
+        let cases = [
+            (
+                &["ALLOCATED", "DRAIN"][..],
+                "DRAIN",
+                "DRAIN ALLOCATED",
+                "DRN+ALLOC",
+            ),
+            (&["IDLE", "DRAIN"], "DRAIN", "DRAIN IDLE", "DRN+IDLE"),
+            (
+                &["MIXED", "COMPLETING"],
+                "MIXED",
+                "MIXED COMPLETING",
+                "MIX+COMP",
+            ),
+            (&["IDLE", "PLANNED"], "IDLE", "IDLE PLANNED", "IDLE+PLND"),
+            (
+                &["IDLE", "DRAIN", "NOT_RESPONDING"],
+                "NOT_RESPONDING",
+                "NOT_RESPONDING IDLE,DRAIN",
+                "RESP?+IDLE+DRN",
+            ),
+            (&["DOWN", "DRAIN"], "DOWN", "DOWN DRAIN", "DOWN+DRN"),
+            (&["ALLOCATED"], "ALLOCATED", "ALLOCATED", "ALLOC"),
+            // Unknown states rank below every known one
+            (
+                &["NEW_FLAG", "MIXED"],
+                "MIXED",
+                "MIXED NEW_FLAG",
+                "MIX+NEW_FLAG",
+            ),
+        ];
+        for (node_states, dominant, long, short) in cases {
+            let node_states = states(node_states);
+            assert_eq!(dominant_state(&node_states), Some(dominant));
+            let format = |short| format_state(&node_states, short, None, Palette::Default);
+            assert_eq!(format(false), long);
+            assert_eq!(format(true), short);
+        }
+        assert_eq!(dominant_state(&[]), None);
+        assert_eq!(format_state(&[], false, None, Palette::Default), "-");
+    }
+
+    #[test]
+    fn colored_numeric_columns_stay_aligned() {
+        static ANSI: Lazy<Regex> = Lazy::new(|| Regex::new("\x1b\\[[0-9;]*m").unwrap());
+        let red = |text: &str| format!("\x1b[31m{}\x1b[0m", text);
+        let rows = [
+            ["hostname", "state", "cpus_available", "memory_available"].map(str::to_owned),
+            [
+                "gpu-1".into(),
+                red("DOWN"),
+                red("3/64"),
+                "10.0/500.0G".into(),
+            ],
+            [
+                "gpu-10".into(),
+                "IDLE".into(),
+                "120/128".into(),
+                red("0.5/1000.0G"),
+            ],
+        ];
+        for (style, border) in [
+            (TableStyle::Markdown, '|'),
+            (TableStyle::Ascii, '|'),
+            (TableStyle::Modern, '│'),
+        ] {
+            let mut table = tabled::builder::Builder::from_iter(rows.clone()).build();
+            align_numeric_columns(&mut table);
+            apply_style_to_table(Some(style), &mut table);
+            let plain = ANSI.replace_all(&table.to_string(), "").into_owned();
+            let rows: Vec<Vec<&str>> = plain
+                .lines()
+                .filter(|line| line.starts_with(border) && line.contains(char::is_alphanumeric))
+                .map(|line| line.trim_matches(border).split(border).collect())
+                .collect();
+            assert_eq!(rows.len(), 3, "\n{}", plain);
+            // Color codes take no room, so every row has the header's column widths
+            let widths = |row: &[&str]| row.iter().map(|cell| cell.chars().count()).collect();
+            let header: Vec<usize> = widths(&rows[0]);
+            for row in &rows {
+                assert_eq!(widths(row), header, "\n{}", plain);
+            }
+            assert_eq!(
+                rows[1..].iter().map(|row| row[2]).collect::<Vec<_>>(),
+                ["           3/64 ", "        120/128 "],
+                "\n{}",
+                plain
+            );
+            assert_eq!(rows[1][0], " gpu-1    ", "\n{}", plain);
+            assert_eq!(rows[2][3], "      0.5/1000.0G ", "\n{}", plain);
+        }
+    }
+
+    #[test]
+    fn accessible_rendering() {
+        colored::control::set_override(false);
+        let jobs = [job(json!({ "gres_detail": ["gpu:a100:1(IDX:4)"] }))];
+        let node = status_with_jobs(
+            json!({
+                "state": ["MIXED", "DRAIN"],
+                "cpus": 64,
+                "alloc_idle_cpus": 48,
+                "alloc_memory": 128000,
+                "gres": "gpu:a100:8",
+                "gres_used": "gpu:a100:5(IDX:0-4)",
+            }),
+            &jobs,
+        );
+        let options = DisplayOptions {
+            accessible: true,
+            ..display_options(Ratio::Free)
+        };
+        assert_eq!(
+            format_gres_cell(&node, &options),
+            "4 used, 1 preempted, 3 idle"
+        );
+        let cli = Cli::parse_from(["lsgres", "gpu", "--accessible"]);
+        let mut table = Table::new([TableNode::from_status(&node, &options)]);
+        hide_unselected_columns(&cli.columns(), &mut table);
+        apply_style_to_table(Some(TableStyle::Ascii), &mut table);
+        align_numeric_columns(&mut table);
+        let expected = [
+            "+----------+----------------+------------------+-------------+-----------------------------+-------------+",
+            "| hostname | cpus_available | memory_available | gres        | gres_status                 | state       |",
+            "+----------+----------------+------------------+-------------+-----------------------------+-------------+",
+            "| gpu-1    |          48/64 |     375.0/500.0G | gpu:a100 ×8 | 4 used, 1 preempted, 3 idle | DRAIN MIXED |",
+            "+----------+----------------+------------------+-------------+-----------------------------+-------------+",
+        ];
+        assert_eq!(table.to_string(), expected.join("\n"));
+        // Categories without GRES other than used, preempted and idle are left out
+        let drained = status(json!({
+            "gres": "gpu:a100:8",
+            "gres_used": "gpu:a100:2(IDX:0-1)",
+            "gres_drain": "gpu:a100:1(IDX:7)",
+        }));
+        assert_eq!(
+            format_gres_cell(&drained, &options),
+            "2 used, 0 preempted, 5 idle, 1 drained"
+        );
+    }
+
+    #[test]
+    fn no_consume_gres_are_skipped() {
+        let models = |s: &str, gres_type: &str| -> Vec<(String, usize)> {
+            GresStatus::parse_list(s, gres_type)
+                .into_iter()
+                .map(|status| (status.model, status.count))
+                .collect()
+        };
+        let gres = "gpu:a100:4,mps:no_consume:100";
+        assert_eq!(models(gres, GPU_TYPE), [("gpu:a100".into(), 4)]);
+        assert!(models(gres, "mps").is_empty());
+        assert_eq!(
+            models("gpu:a100:no_consume:2,gpu:a100:4(S:0)", GPU_TYPE),
+            [("gpu:a100".into(), 4)]
+        );
+        let node = status(json!({
+            "gres": gres,
+            "gres_used": "gpu:a100:1(IDX:0),mps:0",
+        }));
+        assert_eq!((node.gres_total, node.gres_used, node.gres_idle), (4, 1, 3));
+    }
+
+    #[test]
+    fn interactive_conflicts_with_dry_run() {
+        // Picking the filters queries the nodes, which a dry run must not do
+        let parsed = Cli::try_parse_from(["lsgres", "--interactive", "--dry-run"]);
+        assert!(parsed.is_err_and(|err| err.kind() == clap::error::ErrorKind::ArgumentConflict));
+        assert!(Cli::try_parse_from(["lsgres", "--interactive"]).is_ok());
+        assert!(Cli::try_parse_from(["lsgres", "gpu", "--dry-run"]).is_ok());
+    }
+
+    #[test]
+    fn mig_and_whole_gpus() {
+        for model in ["gpu:a100_1g.5gb", "gpu:a100_3g.20gb", "gpu:a100-2g.10gb"] {
+            assert!(is_mig_model(model), "{}", model);
+        }
+        for model in ["gpu:a100", "gpu:h100_nvl", "gpu:a100-sxm4-80gb", "gpu"] {
+            assert!(!is_mig_model(model), "{}", model);
+        }
+        assert_eq!(
+            parse_gres("gpu:a100_1g.5gb:28(IDX:0-27)"),
+            ("gpu:a100_1g.5gb".into(), 28, Some("0-27".into()))
+        );
+        let statuses = [
+            status(json!({
+                "hostname": "gpu-1",
+                "gres": "gpu:a100_1g.5gb:28",
+                "gres_used": "gpu:a100_1g.5gb:10(IDX:0-9)",
+            })),
+            status(json!({
+                "hostname": "gpu-2",
+                "gres": "gpu:a100:8",
+                "gres_used": "gpu:a100:3(IDX:0-2)",
+            })),
+            status(json!({
+                "hostname": "gpu-3",
+                "gres": "gpu:a100_3g.20gb:4,gpu:a100:2",
+                "gres_used": "gpu:a100_3g.20gb:1(IDX:0),gpu:a100:0(IDX:N/A)",
+            })),
+        ];
+        assert_eq!(
+            model_counts(&statuses[2]),
+            [
+                ("gpu:a100_3g.20gb".into(), 4, 1, 3),
+                ("gpu:a100".into(), 2, 0, 2)
+            ]
+        );
+        // The physical card matches its slices, and a slice profile only the slices
+        let matching = |names: &str| -> Vec<&str> {
+            let filters = GresFilter::parse_list(names, None).unwrap();
+            statuses
+                .iter()
+                .filter(|node| {
+                    node.models
+                        .iter()
+                        .any(|model| filters.iter().any(|filter| filter.matches(&model.model)))
+                })
+                .map(|node| node.hostname.as_str())
+                .collect()
+        };
+        assert_eq!(matching("a100"), ["gpu-1", "gpu-2", "gpu-3"]);
+        assert_eq!(matching("1g.5gb"), ["gpu-1"]);
+        assert_eq!(matching("a100_3g"), ["gpu-3"]);
+        let summary = GresSummary::from_statuses(&statuses, false);
+        assert_eq!(
+            summary.one_line(),
+            "Idle GPUs and MIG slices: 28/42 (a100:7, a100_1g.5gb:18, a100_3g.20gb:3)"
+        );
+        let whole = GresSummary::from_statuses(&statuses[1..2], false);
+        assert_eq!(whole.one_line(), "Idle GPUs: 5/8 (a100:5)");
+    }
+
+    #[test]
+    fn preempted_jobs_on_one_node_add_up() {
+        colored::control::set_override(false);
+        let jobs = [
+            job(json!({ "job_id": 1, "gres_detail": ["gpu:a100:2(IDX:0-1)"] })),
+            job(json!({ "job_id": 2, "gres_detail": ["gpu:a100:3(IDX:2-4)"] })),
+        ];
+        let render = |gres_used: &str| {
+            let node = status_with_jobs(
+                json!({ "gres": "gpu:a100:8", "gres_used": gres_used }),
+                &jobs,
+            );
+            format_gres_cell(&node, &display_options(Ratio::Free))
+        };
+        let rendered = render("gpu:a100:6(IDX:0-5)");
+        assert_eq!(rendered.matches('p').count(), 5, "{}", rendered);
+        assert_eq!(rendered, "upppppii");
+        // Preempted GRES never exceed the used ones
+        assert_eq!(render("gpu:a100:3(IDX:0-2)"), "pppiiiii");
+    }
 }
@@ -0,0 +1,35 @@
+use anyhow::{bail, Result};
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Ask on the terminal to pick one of `options` by its number, returning
+/// `None` for an empty answer if `optional` is set
+pub fn pick<'a>(question: &str, options: &'a [String], optional: bool) -> Result<Option<&'a str>> {
+    if !std::io::stdin().is_terminal() {
+        bail!("Picking interactively needs a terminal on stdin");
+    }
+    if options.is_empty() {
+        return Ok(None);
+    }
+    let mut stderr = std::io::stderr();
+    writeln!(stderr, "{}", question)?;
+    for (i, option) in options.iter().enumerate() {
+        writeln!(stderr, "  {:>2}) {}", i + 1, option)?;
+    }
+    let hint = if optional { ", or Enter for all" } else { "" };
+    loop {
+        write!(stderr, "Number{}: ", hint)?;
+        stderr.flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+            bail!("No choice made");
+        }
+        let answer = answer.trim();
+        if answer.is_empty() && optional {
+            return Ok(None);
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=options.len()).contains(&n) => return Ok(Some(&options[n - 1])),
+            _ => writeln!(stderr, "Enter a number from 1 to {}", options.len())?,
+        }
+    }
+}
@@ -0,0 +1,443 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil inverse
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert a civil date to days since the Unix epoch
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Format a Unix timestamp as RFC 3339 in UTC, e.g. `2024-05-01T13:45:00Z`
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// A transition of a POSIX TZ rule on a weekday (0 for Sunday) of a week of the month,
+/// where week 5 is the last one, e.g. `M3.5.0/3` for 3:00 on the last Sunday of March
+struct Transition {
+    month: u32,
+    week: u32,
+    weekday: u32,
+    /// Seconds after local midnight, 2:00 unless given
+    time: i64,
+}
+
+impl Transition {
+    fn parse(s: &str) -> Option<Self> {
+        let (date, time) = match s.split_once('/') {
+            Some((date, time)) => match parse_offset(time)? {
+                (time, "") => (date, time),
+                _ => return None,
+            },
+            None => (s, 7200),
+        };
+        let mut fields = date.strip_prefix('M')?.split('.').map(str::parse::<u32>);
+        let mut field = || fields.next()?.ok();
+        Some(Self {
+            month: field().filter(|month| (1..=12).contains(month))?,
+            week: field().filter(|week| (1..=5).contains(week))?,
+            weekday: field().filter(|&weekday| weekday < 7)?,
+            time,
+        })
+    }
+
+    /// Unix time of the transition in `year`, where local time is `offset` ahead of UTC
+    fn at(&self, year: i64, offset: i64) -> i64 {
+        let first = days_from_civil(year, self.month, 1);
+        let next = match self.month {
+            12 => days_from_civil(year + 1, 1, 1),
+            month => days_from_civil(year, month + 1, 1),
+        };
+        // The Unix epoch was a Thursday
+        let first_weekday = (first + 4).rem_euclid(7);
+        let mut day = first
+            + (i64::from(self.weekday) - first_weekday).rem_euclid(7)
+            + 7 * (i64::from(self.week) - 1);
+        while day >= next {
+            day -= 7;
+        }
+        day * 86400 + self.time - offset
+    }
+}
+
+/// Daylight saving time of a POSIX TZ rule
+struct Dst {
+    offset: i64,
+    start: Transition,
+    end: Transition,
+}
+
+/// A POSIX TZ rule such as `CET-1CEST,M3.5.0,M10.5.0/3`, as in `TZ` or the footer of a
+/// TZif file, with offsets in seconds ahead of UTC
+struct Rule {
+    offset: i64,
+    dst: Option<Dst>,
+}
+
+/// Skip a time zone abbreviation such as `CET` or `<+0530>`
+fn skip_abbreviation(s: &str) -> Option<&str> {
+    if let Some(quoted) = s.strip_prefix('<') {
+        return quoted.split_once('>').map(|(_, rest)| rest);
+    }
+    let end = s
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(s.len());
+    (end >= 3).then(|| &s[end..])
+}
+
+/// Parse `[+-]hh[:mm[:ss]]` into seconds, returning the rest of `s`
+fn parse_offset(s: &str) -> Option<(i64, &str)> {
+    let (sign, s) = match s.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let end = s
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(s.len());
+    if end == 0 {
+        return None;
+    }
+    let mut secs = 0;
+    for (part, unit) in s[..end].split(':').zip([3600, 60, 1]) {
+        secs += part.parse::<i64>().ok()? * unit;
+    }
+    Some((sign * secs, &s[end..]))
+}
+
+impl Rule {
+    fn parse(s: &str) -> Option<Self> {
+        // POSIX offsets count hours west of UTC
+        let (offset, rest) = parse_offset(skip_abbreviation(s)?)?;
+        let offset = -offset;
+        if rest.is_empty() {
+            return Some(Self { offset, dst: None });
+        }
+        let rest = skip_abbreviation(rest)?;
+        let (dst_offset, rest) = match parse_offset(rest) {
+            Some((dst_offset, rest)) => (-dst_offset, rest),
+            None => (offset + 3600, rest),
+        };
+        let (start, end) = rest.strip_prefix(',')?.split_once(',')?;
+        Some(Self {
+            offset,
+            dst: Some(Dst {
+                offset: dst_offset,
+                start: Transition::parse(start)?,
+                end: Transition::parse(end)?,
+            }),
+        })
+    }
+
+    fn offset(&self, secs: i64) -> i64 {
+        let Some(ref dst) = self.dst else {
+            return self.offset;
+        };
+        let (year, _, _) = civil_from_days((secs + self.offset).div_euclid(86400));
+        let start = dst.start.at(year, self.offset);
+        let end = dst.end.at(year, dst.offset);
+        // Daylight saving time spans the new year on the southern hemisphere
+        let in_dst = if start < end {
+            start <= secs && secs < end
+        } else {
+            !(end <= secs && secs < start)
+        };
+        if in_dst {
+            dst.offset
+        } else {
+            self.offset
+        }
+    }
+}
+
+/// A time zone as described by a TZif file
+struct Zone {
+    /// Unix times from which each UTC offset applies, in order
+    transitions: Vec<(i64, i64)>,
+    /// Offset before the first transition
+    initial: i64,
+    /// Rule for times after the last transition
+    rule: Option<Rule>,
+}
+
+impl Zone {
+    const UTC: Self = Self {
+        transitions: Vec::new(),
+        initial: 0,
+        rule: None,
+    };
+
+    fn from_rule(rule: Rule) -> Self {
+        Self {
+            transitions: Vec::new(),
+            initial: rule.offset,
+            rule: Some(rule),
+        }
+    }
+
+    /// Parse a TZif file as described in RFC 8536, using the 64-bit data of version 2
+    /// and later
+    fn parse(data: &[u8]) -> Option<Self> {
+        // Counts of UT indicators, standard indicators, leap seconds, transitions,
+        // local time types and abbreviation characters
+        let header = |data: &[u8]| -> Option<(u8, [usize; 6])> {
+            if data.get(..4)? != b"TZif" {
+                return None;
+            }
+            let mut counts = [0; 6];
+            for (i, count) in counts.iter_mut().enumerate() {
+                let bytes = data.get(20 + 4 * i..24 + 4 * i)?;
+                *count = u32::from_be_bytes(bytes.try_into().ok()?) as usize;
+            }
+            Some((*data.get(4)?, counts))
+        };
+        let (version, [isut, isstd, leap, time, types, chars]) = header(data)?;
+        let (data, time_size) = if version >= b'2' {
+            let v1_size = time * 5 + types * 6 + chars + leap * 8 + isstd + isut;
+            (data.get(44 + v1_size..)?, 8)
+        } else {
+            (data, 4)
+        };
+        let (_, [isut, isstd, leap, time, types, chars]) = header(data)?;
+        let body = data.get(44..)?;
+        let types_start = time * (time_size + 1);
+        let type_data = body.get(types_start..types_start + types * 6)?;
+        let type_offset = |index: usize| -> Option<i64> {
+            let bytes = type_data.get(index * 6..index * 6 + 4)?;
+            Some(i32::from_be_bytes(bytes.try_into().ok()?).into())
+        };
+        let transitions = body
+            .get(..time * time_size)?
+            .chunks_exact(time_size)
+            .zip(body.get(time * time_size..types_start)?)
+            .map(|(at, &index)| {
+                let at = match time_size {
+                    8 => i64::from_be_bytes(at.try_into().ok()?),
+                    _ => i32::from_be_bytes(at.try_into().ok()?).into(),
+                };
+                Some((at, type_offset(index.into())?))
+            })
+            .collect::<Option<Vec<(i64, i64)>>>()?;
+        let footer_start = types_start + types * 6 + chars + leap * (time_size + 4) + isstd + isut;
+        let rule = body
+            .get(footer_start..)
+            .filter(|_| version >= b'2')
+            .and_then(|footer| std::str::from_utf8(footer).ok())
+            .map(str::trim)
+            .filter(|footer| !footer.is_empty())
+            .and_then(Rule::parse);
+        Some(Self {
+            transitions,
+            initial: type_offset(0)?,
+            rule,
+        })
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        Self::parse(&std::fs::read(path).ok()?)
+    }
+
+    /// Offset from UTC in seconds at a Unix time
+    fn offset(&self, secs: i64) -> i64 {
+        let after = self.transitions.partition_point(|&(at, _)| at <= secs);
+        match (after.checked_sub(1), &self.rule) {
+            (_, Some(rule)) if after == self.transitions.len() => rule.offset(secs),
+            (Some(last), _) => self.transitions[last].1,
+            (None, _) => self.initial,
+        }
+    }
+}
+
+/// The local time zone named by `TZ`, or else `/etc/localtime`
+fn load_local_zone() -> Option<Zone> {
+    let Ok(tz) = std::env::var("TZ") else {
+        return Zone::read(Path::new("/etc/localtime"));
+    };
+    // `TZ=:Europe/Berlin` names the same file as `TZ=Europe/Berlin`
+    let name = tz.strip_prefix(':').unwrap_or(&tz);
+    if name.is_empty() {
+        return Some(Zone::UTC);
+    }
+    Zone::read(&Path::new("/usr/share/zoneinfo").join(name))
+        .or_else(|| Rule::parse(name).map(Zone::from_rule))
+}
+
+/// Offset of the local time zone from UTC in seconds at a Unix time, zero if it
+/// cannot be determined. The time zone is only read once.
+fn local_offset(secs: u64) -> i64 {
+    static ZONE: OnceLock<Option<Zone>> = OnceLock::new();
+    ZONE.get_or_init(load_local_zone)
+        .as_ref()
+        .map_or(0, |zone| zone.offset(secs as i64))
+}
+
+/// Format a Unix timestamp in the local time zone, e.g. `2024-05-01 15:45:00 +0200`
+pub fn format_local_timestamp(secs: u64) -> String {
+    let offset = local_offset(secs);
+    let local = secs as i64 + offset;
+    let days = local.div_euclid(86400);
+    let rem = local.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 3600,
+        offset.abs() % 3600 / 60
+    )
+}
+
+/// Humanize a duration in seconds, e.g. `3d4h`, `2h13m` or `5m`
+pub fn format_duration(secs: u64) -> String {
+    let days = secs / 86400;
+    let hours = secs % 86400 / 3600;
+    let minutes = secs % 3600 / 60;
+    if days > 0 {
+        format!("{}d{}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h{}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unix time of a UTC date and time
+    fn utc(year: i64, month: u32, day: u32, hour: i64) -> i64 {
+        days_from_civil(year, month, day) * 86400 + hour * 3600
+    }
+
+    #[test]
+    fn days_from_civil_inverts_civil_from_days() {
+        for days in [-719468, -1, 0, 19723, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn rule_without_dst() {
+        assert_eq!(Rule::parse("UTC0").unwrap().offset(0), 0);
+        assert_eq!(Rule::parse("<+0530>-5:30").unwrap().offset(0), 19800);
+        assert_eq!(Rule::parse("<-03>3").unwrap().offset(0), -10800);
+    }
+
+    #[test]
+    fn rule_with_dst() {
+        let rule = Rule::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(rule.offset(utc(2024, 1, 15, 12)), 3600);
+        assert_eq!(rule.offset(utc(2024, 7, 1, 12)), 7200);
+        // Summer time started at 1:00 UTC on 31 March 2024 and ended on 27 October
+        assert_eq!(rule.offset(utc(2024, 3, 31, 1) - 1), 3600);
+        assert_eq!(rule.offset(utc(2024, 3, 31, 1)), 7200);
+        assert_eq!(rule.offset(utc(2024, 10, 27, 1) - 1), 7200);
+        assert_eq!(rule.offset(utc(2024, 10, 27, 1)), 3600);
+        let rule = Rule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(rule.offset(utc(2024, 1, 15, 12)), -18000);
+        assert_eq!(rule.offset(utc(2024, 7, 1, 12)), -14400);
+    }
+
+    #[test]
+    fn rule_with_dst_over_new_year() {
+        let rule = Rule::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(rule.offset(utc(2024, 1, 15, 12)), 39600);
+        assert_eq!(rule.offset(utc(2024, 7, 1, 12)), 36000);
+        assert_eq!(rule.offset(utc(2024, 12, 25, 12)), 39600);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        for rule in [
+            "",
+            "X5",
+            "CET",
+            "CET-1CEST,M3.5.0",
+            "CET-1CEST,M13.5.0,M10.5.0",
+        ] {
+            assert!(Rule::parse(rule).is_none(), "{}", rule);
+        }
+    }
+
+    /// A version 2 TZif file with an empty version 1 part
+    fn tzif(transitions: &[(i64, u8)], offsets: &[i32], footer: &str) -> Vec<u8> {
+        let header = |times: usize, types: usize| {
+            let mut header = b"TZif2".to_vec();
+            header.resize(20, 0);
+            for count in [0, 0, 0, times, types, 0] {
+                header.extend((count as u32).to_be_bytes());
+            }
+            header
+        };
+        let mut data = header(0, 0);
+        data.extend(header(transitions.len(), offsets.len()));
+        for &(at, _) in transitions {
+            data.extend(at.to_be_bytes());
+        }
+        data.extend(transitions.iter().map(|&(_, index)| index));
+        for &offset in offsets {
+            data.extend(offset.to_be_bytes());
+            data.extend([0, 0]);
+        }
+        data.extend(format!("\n{}\n", footer).bytes());
+        data
+    }
+
+    #[test]
+    fn zone_from_tzif() {
+        let zone = Zone::parse(&tzif(&[(1000, 1), (2000, 0)], &[3600, 7200], "")).unwrap();
+        assert_eq!(zone.offset(999), 3600);
+        assert_eq!(zone.offset(1000), 7200);
+        assert_eq!(zone.offset(2000), 3600);
+        // The footer rule applies after the last transition
+        let zone = Zone::parse(&tzif(&[(1000, 1)], &[0, 7200], "<+03>-3")).unwrap();
+        assert_eq!(zone.offset(1500), 10800);
+        assert!(Zone::parse(b"not a TZif file").is_none());
+    }
+}